@@ -1,40 +1,91 @@
-use crossterm::event::{Event, KeyEvent, KeyEventKind, read};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, poll, read};
 use std::{
-    env,
-    io::Error,
+    cell::RefCell,
+    env, fs,
+    io::{Error, ErrorKind},
+    iter,
     panic::{set_hook, take_hook},
+    path::Path,
+    ptr,
+    rc::Rc,
+    time::Duration,
 };
 
+mod bufferlist;
+mod clipboard;
 mod command;
+mod commandbar;
 mod documentstatus;
 mod fileinfo;
+mod layout;
 mod messagebar;
+mod options;
+mod register;
 mod statusbar;
+mod tabline;
 mod terminal;
 mod uicomponent;
+mod undotree;
 mod view;
 
-use command::{EditorCommand, Mode};
+use bufferlist::BufferList;
+use command::{
+    CommandLineCommand, Counted, EditorCommand, ExCommand, InsertCommand, LastChange, MacroCommand,
+    MacroRecorder, Mode, Motion, NormalCommand, Operator, PendingKeys, TabCommand, Target,
+    VisualCommand, WindowCommand, keys_from_text,
+};
+use commandbar::{CommandBar, PromptType};
 use documentstatus::DocumentStatus;
+use fileinfo::Encoding;
+use layout::{Layout, SplitDirection};
 use messagebar::MessageBar;
+use options::Options;
+use register::{Register, RegisterKind, Registers};
 use statusbar::StatusBar;
-use terminal::Size;
-use terminal::Terminal;
+use tabline::TabLine;
+use terminal::{Position, Size, Terminal};
 use uicomponent::UIComponent;
-use view::View;
+use undotree::UndoTree;
+use view::{Buffer, Jump, View, ViewState};
 
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+const QUIT_TIMES: u8 = 3;
+/// How long to wait for the next key of an unfinished Normal mode command
+const KEY_TIMEOUT: Duration = Duration::from_secs(1);
+/// How deep macros can play each other, like vim's `maxmapdepth`
+const MAX_MACRO_DEPTH: usize = 1000;
 
 #[derive(Default)]
 pub struct Editor {
     should_quit: bool,
+    quit_times: u8,
     mode: Mode,
-    view: View,
+    pending_keys: PendingKeys,
+    /// The tab pages, each with its own windows
+    tabs: Vec<Layout>,
+    tab: usize,
+    tab_line: TabLine,
+    buffers: BufferList,
     status_bar: StatusBar,
     message_bar: MessageBar,
+    command_bar: CommandBar,
+    prompt_type: PromptType,
+    undo_tree: UndoTree,
+    show_undo_tree: bool,
     terminal_size: Size,
     title: String,
+    options: Options,
+    registers: Registers,
+    /// The text typed since entering Insert mode, for the `".` register
+    inserted: String,
+    last_change: LastChange,
+    macro_recorder: MacroRecorder,
+    /// How many macros are being played, one inside the other. The keys
+    /// they type aren't recorded.
+    playing: usize,
+    /// Set when a motion fails, which stops playing macros
+    motion_failed: bool,
 }
 
 impl Editor {
@@ -47,17 +98,34 @@ impl Editor {
         Terminal::initialize()?;
 
         let mut editor = Self::default();
+        editor.tabs.push(Layout::default());
         let size = Terminal::size().unwrap_or_default();
         editor.resize(size);
 
-        let args: Vec<String> = env::args().collect();
-        if let Some(file_name) = args.get(1) {
-            editor.view.load(file_name);
-        }
-
         editor
             .message_bar
             .update_message("HELP: Ctrl-S = save | Ctrl-Q = quit".to_string());
+
+        editor.buffers.push(Rc::clone(editor.view().buffer()));
+        let mut first = None;
+        let mut errors = Vec::new();
+        for file_name in env::args().skip(1) {
+            match editor.add_file(&file_name) {
+                Ok(added) => first = first.or(Some(added)),
+                Err(err) => errors.push(err),
+            }
+        }
+        // The errors of files that could not be loaded are shown after the
+        // message of the first file, so that neither hides the other
+        let mut messages = Vec::new();
+        if let Some((index, message)) = first {
+            editor.switch_to_buffer(index);
+            messages.push(message);
+        }
+        messages.extend(errors);
+        if !messages.is_empty() {
+            editor.message_bar.update_message(messages.join("  "));
+        }
         editor.refresh_status();
 
         Ok(editor)
@@ -70,12 +138,17 @@ impl Editor {
                 break;
             }
 
-            match read() {
+            match self.next_event() {
                 Ok(event) => {
-                    self.evaluate_event(event);
-                    let mut status = self.view.get_status();
-                    status.mode = self.mode;
-                    self.status_bar.update_status(status);
+                    match event {
+                        Some(event) => self.evaluate_event(&event),
+                        None => self.pending_keys.clear(),
+                    }
+                    self.status_bar.update_status(self.document_status());
+                    self.update_tab_line();
+                    if self.show_undo_tree {
+                        self.undo_tree.update_entries(self.view().undo_tree());
+                    }
                 }
                 Err(err) => {
                     #[cfg(debug_assertions)]
@@ -87,10 +160,39 @@ impl Editor {
         }
     }
 
+    /// Waits for the next event. While a command is unfinished the wait is
+    /// cut short after `KEY_TIMEOUT`, returning `None`.
+    fn next_event(&self) -> Result<Option<Event>, Error> {
+        if self.pending_keys.is_empty() || poll(KEY_TIMEOUT)? {
+            read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
     fn resize(&mut self, size: Size) {
         self.terminal_size = size;
-        self.view.resize(Size {
-            height: size.height.saturating_sub(2),
+        let text_height = size.height.saturating_sub(2);
+        let undo_tree_height = self.undo_tree_height();
+        let tab_line_height = self.tab_line_height();
+
+        let layout_size = Size {
+            height: text_height
+                .saturating_sub(undo_tree_height)
+                .saturating_sub(tab_line_height),
+            width: size.width,
+        };
+        for layout in &mut self.tabs {
+            layout.resize(tab_line_height, layout_size);
+        }
+
+        self.tab_line.resize(Size {
+            height: tab_line_height,
+            width: size.width,
+        });
+
+        self.undo_tree.resize(Size {
+            height: undo_tree_height,
             width: size.width,
         });
 
@@ -99,14 +201,76 @@ impl Editor {
             width: size.width,
         });
 
+        self.command_bar.resize(Size {
+            height: 1,
+            width: size.width,
+        });
+
         self.status_bar.resize(Size {
             height: 1,
             width: size.width,
         });
     }
 
+    fn undo_tree_height(&self) -> usize {
+        if self.show_undo_tree {
+            #[allow(clippy::integer_division)]
+            let height = self.terminal_size.height.saturating_sub(2) / 3;
+            height
+        } else {
+            0
+        }
+    }
+
+    /// Like vim, the tab line is only shown when there are several tab pages
+    fn tab_line_height(&self) -> usize {
+        usize::from(self.tabs.len() > 1)
+    }
+
+    fn toggle_undo_tree(&mut self) {
+        self.show_undo_tree = !self.show_undo_tree;
+        if self.show_undo_tree {
+            self.undo_tree.update_entries(self.view().undo_tree());
+        }
+        self.resize(self.terminal_size);
+    }
+
+    /// The windows of the current tab page
+    fn layout(&self) -> &Layout {
+        // There is always at least one tab page
+        self.tabs.get(self.tab).unwrap_or_else(|| unreachable!())
+    }
+
+    fn layout_mut(&mut self) -> &mut Layout {
+        Self::tab_mut(&mut self.tabs, self.tab)
+    }
+
+    /// Like `layout_mut`, for when other fields are borrowed at the same time
+    fn tab_mut(tabs: &mut [Layout], tab: usize) -> &mut Layout {
+        tabs.get_mut(tab).unwrap_or_else(|| unreachable!())
+    }
+
+    /// The window the cursor is in
+    fn view(&self) -> &View {
+        self.layout().active()
+    }
+
+    fn view_mut(&mut self) -> &mut View {
+        self.layout_mut().active_mut()
+    }
+
+    fn document_status(&self) -> DocumentStatus {
+        let mut status = self.view().get_status();
+        status.mode = self.mode;
+        status.pending_keys = self.pending_keys.to_string();
+        status.recording = self.macro_recorder.recording();
+        status.buffer_number = self.current_buffer().saturating_add(1);
+        status.buffer_count = self.buffers.len();
+        status
+    }
+
     fn refresh_status(&mut self) {
-        let status = self.view.get_status();
+        let status = self.document_status();
         let title = format!("{} - {NAME}", status.file_name);
 
         self.status_bar.update_status(status);
@@ -122,44 +286,70 @@ impl Editor {
         }
 
         let _ = Terminal::hide_caret();
-        self.message_bar
-            .render(self.terminal_size.height.saturating_sub(1));
+        let bottom_row = self.terminal_size.height.saturating_sub(1);
+        if self.mode == Mode::Command {
+            self.command_bar.render(bottom_row);
+        } else {
+            self.message_bar.render(bottom_row);
+        }
         if self.terminal_size.height > 1 {
             self.status_bar
                 .render(self.terminal_size.height.saturating_sub(2));
         }
 
         if self.terminal_size.height > 2 {
-            self.view.render(0);
+            if self.tab_line_height() > 0 {
+                self.tab_line.render(0);
+            }
+            self.layout_mut().render();
+        }
+        let undo_tree_height = self.undo_tree_height();
+        if undo_tree_height > 0 {
+            self.undo_tree.render(
+                self.terminal_size
+                    .height
+                    .saturating_sub(2)
+                    .saturating_sub(undo_tree_height),
+            );
         }
-        let _ = Terminal::move_caret_to(self.view.caret_position());
+        let caret_position = if self.mode == Mode::Command {
+            Position {
+                col: self.command_bar.caret_col(),
+                row: bottom_row,
+            }
+        } else {
+            self.layout().caret_position()
+        };
+        let _ = Terminal::move_caret_to(caret_position);
         let _ = Terminal::show_caret();
         let _ = Terminal::execute();
     }
 
-    fn evaluate_event(&mut self, event: Event) {
-        let should_process = match &event {
-            Event::Key(KeyEvent { kind, .. }) => kind == &KeyEventKind::Press,
+    fn evaluate_event(&mut self, event: &Event) {
+        let should_process = match event {
+            Event::Key(KeyEvent { kind, .. }) => *kind == KeyEventKind::Press,
             Event::Resize(_, _) => true,
             _ => false,
         };
 
         if should_process {
-            match EditorCommand::try_from(event, &self.mode) {
-                Ok(command) => match command {
-                    EditorCommand::Quit => self.should_quit = true,
-                    EditorCommand::Esc => self.mode = Mode::Normal,
-                    EditorCommand::Change(mode) => self.mode = mode,
-                    _ => {
-                        self.view.handle_command(command);
-                        if let EditorCommand::Resize(size) = command {
-                            self.resize(size);
-                        }
-                    }
-                },
-                Err(_) => {
-                    // Silently ignore all unwanted key presses
-                }
+            // Silently ignore all unwanted key presses
+            let counted = self.parse_event(event);
+            let stops_recording = matches!(
+                counted,
+                Some(Counted {
+                    command: EditorCommand::Macro(MacroCommand::Stop),
+                    ..
+                })
+            );
+            if let Event::Key(key) = event
+                && self.playing == 0
+                && !stops_recording
+            {
+                self.macro_recorder.push(*key);
+            }
+            if let Some(counted) = counted {
+                self.execute(counted);
             }
         } else {
             #[cfg(debug_assertions)]
@@ -168,6 +358,1109 @@ impl Editor {
             }
         }
     }
+
+    /// Runs a command, recording it if it is part of a change to the text
+    fn execute(&mut self, counted: Counted) {
+        let Counted {
+            command,
+            count,
+            register,
+        } = counted;
+        self.record_change(counted);
+        match command {
+            EditorCommand::Normal(NormalCommand::Operate(operator, target)) => {
+                self.operate(operator, target, count, register);
+            }
+            EditorCommand::Normal(NormalCommand::Put { before }) => {
+                self.put(register, before, count);
+            }
+            EditorCommand::Normal(NormalCommand::Repeat) => self.repeat_change(count),
+            EditorCommand::Normal(NormalCommand::OlderJump) => self.follow_jump(count, false),
+            EditorCommand::Normal(NormalCommand::NewerJump) => self.follow_jump(count, true),
+            EditorCommand::Normal(NormalCommand::SetMark(name @ 'A'..='Z')) => {
+                // Only one buffer has a file mark
+                for (buffer, _) in self.buffers.iter() {
+                    buffer.borrow_mut().remove_mark(name);
+                }
+                self.view_mut().set_mark(name);
+            }
+            EditorCommand::Normal(NormalCommand::Motion(motion @ Motion::Mark { name, .. }))
+                if self.mode == Mode::Normal
+                    && name.is_ascii_uppercase()
+                    && self.view().buffer().borrow().mark(name).is_none() =>
+            {
+                self.goto_file_mark(name, motion);
+            }
+            EditorCommand::Normal(command) => {
+                if !self.view_mut().handle_normal_command(command, count) {
+                    self.motion_failed = true;
+                    if let NormalCommand::Motion(Motion::Mark { .. }) = command {
+                        self.message_bar
+                            .update_message(String::from("Mark not set"));
+                    }
+                }
+            }
+            EditorCommand::Visual(command) => {
+                self.handle_visual_command(command, count, register);
+            }
+            EditorCommand::Insert(insert_command) => {
+                self.record_insert(insert_command);
+                self.view_mut().handle_command(command);
+            }
+            EditorCommand::ToggleUndoTree => self.toggle_undo_tree(),
+            EditorCommand::Quit => self.handle_quit(),
+            EditorCommand::Save => self.save(),
+            EditorCommand::Esc => {
+                self.set_mode(Mode::Normal);
+                self.view_mut().handle_command(command);
+            }
+            EditorCommand::Change(mode) => self.set_mode(mode),
+            EditorCommand::CommandLine(command) => self.handle_command_line(command),
+            EditorCommand::Window(command) => self.handle_window_command(command),
+            EditorCommand::Macro(command) => self.handle_macro_command(command, count),
+            // `{N}gt` goes to tab page N
+            EditorCommand::Tab(TabCommand::Next) => self.next_tab(count),
+            EditorCommand::Tab(TabCommand::Previous) => {
+                for _ in 0..count.unwrap_or(1) {
+                    self.previous_tab();
+                }
+            }
+            _ => {
+                self.view_mut().handle_command(command);
+                if let EditorCommand::Resize(size) = command {
+                    self.resize(size);
+                }
+            }
+        }
+        if self.mode != Mode::Insert {
+            self.last_change.finish();
+        }
+    }
+}
+
+impl Editor {
+    /// Turns the event into a command. Normal mode keys are collected until
+    /// they make a whole command, Esc drops them.
+    fn parse_event(&mut self, event: &Event) -> Option<Counted> {
+        // Any other key, even one starting a command, breaks a row of Ctrl-Q
+        if let Event::Key(key) = event
+            && !(key.code == KeyCode::Char('q') && key.modifiers == KeyModifiers::CONTROL)
+        {
+            self.reset_quit_times();
+        }
+        match event {
+            // `q` ends the recording rather than starting another one
+            Event::Key(key)
+                if key.code == KeyCode::Char('q')
+                    && key.modifiers == KeyModifiers::NONE
+                    && self.mode == Mode::Normal
+                    && self.pending_keys.is_empty()
+                    && self.macro_recorder.recording().is_some() =>
+            {
+                Some(Counted::from(EditorCommand::Macro(MacroCommand::Stop)))
+            }
+            Event::Key(key)
+                if matches!(self.mode, Mode::Normal | Mode::Visual) && !key.code.is_esc() =>
+            {
+                self.pending_keys.push(*key, self.mode).ok().flatten()
+            }
+            Event::Key(_) if !self.pending_keys.is_empty() => {
+                self.pending_keys.clear();
+                None
+            }
+            _ => EditorCommand::try_from(event, self.mode)
+                .ok()
+                .map(Counted::from),
+        }
+    }
+
+    /// Applies the operator in the current window, keeping the text yanked or
+    /// deleted in the register `name`, and going on in Insert mode for a change
+    fn operate(
+        &mut self,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+        name: Option<char>,
+    ) {
+        if !self.may_write_register(operator, name) {
+            return;
+        }
+        let mut register = Register::default();
+        let applied = Self::tab_mut(&mut self.tabs, self.tab)
+            .active_mut()
+            .apply_operator(operator, target, count, &mut register, &self.options);
+        if applied {
+            self.store_register(operator, name, register);
+        } else {
+            self.motion_failed = true;
+        }
+        if applied && operator == Operator::Change {
+            self.set_mode(Mode::Insert);
+        }
+    }
+
+    /// Whether `operator` can keep its text in the register `name`
+    fn may_write_register(&mut self, operator: Operator, name: Option<char>) -> bool {
+        let writes = matches!(
+            operator,
+            Operator::Yank | Operator::Delete | Operator::Change
+        );
+        match name {
+            Some(name) if writes && Registers::is_read_only(name) => {
+                self.message_bar
+                    .update_message(format!("Register \"{name} is read-only"));
+                false
+            }
+            _ => true,
+        }
+    }
+
+    fn store_register(&mut self, operator: Operator, name: Option<char>, register: Register) {
+        // Neither the registers nor the clipboard take nothing
+        if register.text.is_empty() {
+            return;
+        }
+        match operator {
+            Operator::Yank => self.registers.yank(name, register),
+            Operator::Delete | Operator::Change => self.registers.delete(name, register),
+            _ => return,
+        }
+        if let Some(name @ ('+' | '*')) = name {
+            let text = self.registers.get(Some(name)).unwrap_or_default().text;
+            if let Err(err) = clipboard::copy(self.options.clipboard, name, &text) {
+                self.message_bar.update_message(err);
+            }
+        }
+    }
+
+    /// The register `name`, with `"%` the name of the current file, and
+    /// `"+` and `"*` what is on the system clipboard if it can be read
+    fn register(&self, name: Option<char>) -> Option<Register> {
+        match name {
+            Some('%') => self.view().file_name().map(|text| Register {
+                text,
+                kind: RegisterKind::Charwise,
+            }),
+            Some(name @ ('+' | '*')) => {
+                let kept = self.registers.get(Some(name));
+                let Some(text) = clipboard::paste(self.options.clipboard, name) else {
+                    return kept;
+                };
+                // Text copied from the editor keeps its kind
+                match kept {
+                    Some(kept) if kept.text == text => Some(kept),
+                    _ => {
+                        let kind = if text.ends_with('\n') {
+                            RegisterKind::Linewise
+                        } else {
+                            RegisterKind::Charwise
+                        };
+                        Some(Register { text, kind })
+                    }
+                }
+            }
+            _ => self.registers.get(name),
+        }
+    }
+
+    /// Puts the text of the register `name`, the unnamed one by default
+    fn put(&mut self, name: Option<char>, before: bool, count: Option<usize>) {
+        let register = self
+            .register(name)
+            .filter(|register| !register.text.is_empty());
+        match register {
+            Some(register) => {
+                self.view_mut().put(&register, before, count);
+            }
+            None => self
+                .message_bar
+                .update_message(format!("Nothing in register {}", name.unwrap_or('"'))),
+        }
+    }
+
+    /// Lists the registers that aren't empty, or only those in `names`.
+    /// `"+` and `"*` show what the editor kept, without running the
+    /// clipboard tool.
+    fn list_registers(&self, names: Option<&str>) -> String {
+        let mut registers = self.registers.list();
+        if let Some(file) = self.register(Some('%')) {
+            registers.push(('%', file));
+        }
+        registers
+            .iter()
+            .filter(|(name, _)| names.is_none_or(|names| names.contains(*name)))
+            .map(|(name, register)| format!("{} \"{name} {register}", register.kind))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// `'A` to a file mark in another buffer
+    fn goto_file_mark(&mut self, name: char, motion: Motion) {
+        let index = self
+            .buffers
+            .iter()
+            .position(|(buffer, _)| buffer.borrow().mark(name).is_some());
+        let Some(index) = index else {
+            self.message_bar
+                .update_message(String::from("Mark not set"));
+            self.motion_failed = true;
+            return;
+        };
+        self.view_mut().record_jump();
+        self.switch_to_buffer(index);
+        self.view_mut().apply_motion(motion, None);
+    }
+
+    /// Ctrl-O, or Ctrl-I with `newer`, which may go to another buffer
+    fn follow_jump(&mut self, count: Option<usize>, newer: bool) {
+        let count = count.unwrap_or(1);
+        let jump = if newer {
+            self.view_mut().newer_jump(count)
+        } else {
+            self.view_mut().older_jump(count)
+        };
+        let Some(Jump { buffer, location }) = jump else {
+            self.motion_failed = true;
+            return;
+        };
+        if !Rc::ptr_eq(&buffer, self.view().buffer()) {
+            let Some(index) = self.buffers.index_of(&buffer) else {
+                return;
+            };
+            self.switch_to_buffer(index);
+        }
+        self.view_mut().goto(location);
+    }
+
+    fn handle_macro_command(&mut self, command: MacroCommand, count: Option<usize>) {
+        match command {
+            MacroCommand::Record(name) => self.macro_recorder.start(name),
+            MacroCommand::Stop => {
+                if let Some((name, keys)) = self.macro_recorder.stop() {
+                    self.registers.record(name, keys);
+                }
+            }
+            MacroCommand::Play(name) => self.play_macro(name, count),
+        }
+    }
+
+    /// `@{register}`, types the keys kept in the register `count` times,
+    /// until a motion fails
+    fn play_macro(&mut self, name: char, count: Option<usize>) {
+        let name = if name == '@' {
+            let Some(last) = self.macro_recorder.last_played() else {
+                self.message_bar
+                    .update_message(String::from("No previously used register"));
+                return;
+            };
+            last
+        } else {
+            name
+        };
+        self.macro_recorder.set_last_played(name);
+        if self.playing >= MAX_MACRO_DEPTH {
+            self.message_bar
+                .update_message(String::from("Macros nested too deep"));
+            self.motion_failed = true;
+            return;
+        }
+
+        let keys = if name == ':' {
+            // `@:` runs the last command line again
+            let command = self.registers.get(Some(':')).unwrap_or_default().text;
+            keys_from_text(&format!(":{command}\r"))
+        } else {
+            keys_from_text(&self.register(Some(name)).unwrap_or_default().text)
+        };
+        if self.playing == 0 {
+            self.motion_failed = false;
+        }
+        self.playing = self.playing.saturating_add(1);
+        'times: for _ in 0..count.unwrap_or(1) {
+            for key in &keys {
+                self.evaluate_event(&Event::Key(*key));
+                if self.motion_failed || self.should_quit {
+                    break 'times;
+                }
+            }
+        }
+        self.playing = self.playing.saturating_sub(1);
+    }
+
+    /// Records the commands making a change to the text, for `.`. Changes
+    /// start in Normal or Visual mode and go on with what is typed in
+    /// Insert mode.
+    fn record_change(&mut self, counted: Counted) {
+        let starts_change = match counted.command {
+            EditorCommand::Normal(NormalCommand::Operate(operator, _))
+            | EditorCommand::Visual(VisualCommand::Operate(operator)) => operator != Operator::Yank,
+            EditorCommand::Normal(NormalCommand::Put { .. })
+            | EditorCommand::Visual(VisualCommand::Join | VisualCommand::Replace(_)) => true,
+            EditorCommand::Change(mode) => mode == Mode::Insert && self.mode == Mode::Normal,
+            _ => false,
+        };
+        if starts_change {
+            let selection = if self.mode == Mode::Visual {
+                self.selection_commands()
+            } else {
+                Vec::new()
+            };
+            self.last_change.start(selection, counted);
+        } else if self.mode == Mode::Insert
+            && matches!(
+                counted.command,
+                EditorCommand::Insert(_) | EditorCommand::Esc
+            )
+        {
+            self.last_change.push(counted);
+        }
+    }
+
+    /// The commands selecting as much text as the current selection from
+    /// where the cursor is, for `.` after a change made in Visual mode
+    fn selection_commands(&self) -> Vec<Counted> {
+        let Some((kind, motions)) = self.view().selection_motions() else {
+            return Vec::new();
+        };
+        let select = Counted::from(EditorCommand::Visual(VisualCommand::Select(kind)));
+        iter::once(select)
+            .chain(motions.into_iter().map(|(motion, count)| {
+                Counted::new(
+                    EditorCommand::Normal(NormalCommand::Motion(motion)),
+                    count,
+                    None,
+                )
+            }))
+            .collect()
+    }
+
+    /// `.`, makes the last change again, with `count` instead of its own
+    fn repeat_change(&mut self, count: Option<usize>) {
+        for command in self.last_change.replay(count) {
+            self.execute(command);
+        }
+    }
+
+    /// Keeps track of the text typed in Insert mode
+    fn record_insert(&mut self, command: InsertCommand) {
+        match command {
+            InsertCommand::Char(ch) => self.inserted.push(ch),
+            InsertCommand::Enter => self.inserted.push('\n'),
+            InsertCommand::Backspace => {
+                self.inserted.pop();
+            }
+            InsertCommand::Delete => {}
+        }
+    }
+
+    /// Starts Visual mode or switches the kind of selection, or applies a
+    /// command to the selection and goes back to Normal mode, or on to
+    /// Insert mode after a change
+    fn handle_visual_command(
+        &mut self,
+        command: VisualCommand,
+        count: Option<usize>,
+        name: Option<char>,
+    ) {
+        match command {
+            VisualCommand::Select(kind) if self.mode != Mode::Visual => {
+                if self.view_mut().start_selection(kind) {
+                    self.set_mode(Mode::Visual);
+                }
+            }
+            VisualCommand::Select(kind) if self.view().selection_kind() == Some(kind) => {
+                self.set_mode(Mode::Normal);
+            }
+            VisualCommand::Select(kind) => self.view_mut().set_selection_kind(kind),
+            VisualCommand::SwapEnds => self.view_mut().swap_selection_ends(),
+            _ => {
+                if let VisualCommand::Operate(operator) = command
+                    && !self.may_write_register(operator, name)
+                {
+                    return;
+                }
+                let mut register = Register::default();
+                let applied = Self::tab_mut(&mut self.tabs, self.tab)
+                    .active_mut()
+                    .apply_to_selection(command, count, &mut register, &self.options);
+                if applied && let VisualCommand::Operate(operator) = command {
+                    self.store_register(operator, name, register);
+                }
+                if applied && matches!(command, VisualCommand::Operate(Operator::Change)) {
+                    self.set_mode(Mode::Insert);
+                } else {
+                    self.set_mode(Mode::Normal);
+                }
+            }
+        }
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        if self.mode == Mode::Visual && mode != Mode::Visual {
+            self.view_mut().end_selection();
+        }
+        if self.mode == Mode::Insert && mode != Mode::Insert {
+            self.view_mut().set_mark('^');
+            let inserted = std::mem::take(&mut self.inserted);
+            self.registers.set_last_inserted(inserted);
+        }
+        if mode == Mode::Command {
+            self.start_prompt(PromptType::Command);
+            return;
+        }
+        if self.mode == Mode::Command {
+            if self.prompt_type == PromptType::SaveAs {
+                self.message_bar
+                    .update_message(String::from("Save aborted."));
+            }
+            self.message_bar.set_needs_redraw(true);
+        }
+        self.mode = mode;
+    }
+
+    fn start_prompt(&mut self, prompt_type: PromptType) {
+        self.prompt_type = prompt_type;
+        self.command_bar.start(prompt_type.prompt());
+        self.mode = Mode::Command;
+    }
+
+    fn handle_command_line(&mut self, command: CommandLineCommand) {
+        match command {
+            CommandLineCommand::Submit => {
+                let input = self.command_bar.value().to_string();
+                let prompt_type = self.prompt_type;
+                // Submitting is not aborting, whatever the prompt
+                self.prompt_type = PromptType::Command;
+                self.set_mode(Mode::Normal);
+                match prompt_type {
+                    PromptType::Command => self.execute_command_line(&input),
+                    PromptType::SaveAs => {
+                        self.save_as(&input, false);
+                    }
+                }
+            }
+            // Backspace on an empty command line leaves it, like vim
+            CommandLineCommand::Backspace if self.command_bar.value().is_empty() => {
+                self.set_mode(Mode::Normal);
+            }
+            _ => self.command_bar.handle_command(command),
+        }
+    }
+
+    fn execute_command_line(&mut self, input: &str) {
+        if input.trim().is_empty() {
+            return;
+        }
+        self.registers.set_last_command(input.trim().to_string());
+        match ExCommand::parse(input) {
+            Ok(command) => self.execute_ex_command(command),
+            Err(err) => self.message_bar.update_message(err),
+        }
+    }
+
+    fn execute_ex_command(&mut self, command: ExCommand) {
+        match command {
+            ExCommand::Write { file: None, .. } => self.save(),
+            ExCommand::Write {
+                file: Some(file),
+                force,
+            } => {
+                self.write(Some(&file), force);
+            }
+            ExCommand::WriteQuit { file, force } => {
+                if self.write(file.as_deref(), force) {
+                    self.quit_window(force);
+                }
+            }
+            ExCommand::Exit => {
+                if !self.view().is_modified() || self.write(None, false) {
+                    self.quit_window(false);
+                }
+            }
+            ExCommand::Quit { force } => self.quit_window(force),
+            ExCommand::Edit { file, force } => {
+                let reload = match (&file, self.view().file_name()) {
+                    (None, _) => true,
+                    (Some(file), Some(current)) => Path::new(file) == Path::new(&current),
+                    (Some(_), None) => false,
+                };
+                if !reload {
+                    self.open(file.as_deref().unwrap_or_default());
+                } else if self.may_discard_changes(force) {
+                    self.reload();
+                }
+            }
+            ExCommand::SaveAs { file, force } => {
+                self.save_as(&file, force);
+            }
+            ExCommand::Set(arguments) => self.set_options(&arguments),
+            ExCommand::HexEdit => {
+                if let Err(err) = self.view_mut().toggle_hex() {
+                    self.message_bar
+                        .update_message(format!("Cannot show the bytes: {err}"));
+                }
+            }
+            ExCommand::BufferNext => self.cycle_buffer(true),
+            ExCommand::BufferPrevious => self.cycle_buffer(false),
+            ExCommand::Buffer(argument) => match self.find_buffer(&argument) {
+                Ok(index) => {
+                    self.view_mut().record_jump();
+                    self.switch_to_buffer(index);
+                }
+                Err(err) => self.message_bar.update_message(err),
+            },
+            ExCommand::ListBuffers => {
+                let list = self.list_buffers();
+                self.message_bar.update_message(list);
+            }
+            ExCommand::Registers(names) => {
+                let list = self.list_registers(names.as_deref());
+                self.message_bar.update_message(list);
+            }
+            ExCommand::Split(file) => self.split(SplitDirection::Horizontal, file.as_deref()),
+            ExCommand::VerticalSplit(file) => {
+                self.split(SplitDirection::Vertical, file.as_deref());
+            }
+            ExCommand::Close { force } => self.close_window(force),
+            ExCommand::TabNew(file) => self.new_tab(file.as_deref()),
+            ExCommand::TabNext(number) => self.next_tab(number),
+            ExCommand::TabPrevious => self.previous_tab(),
+            ExCommand::TabClose { force } => self.close_tab(force),
+            ExCommand::Only => self.layout_mut().only(),
+            ExCommand::Goto(line_number) => self.view_mut().goto_line(line_number),
+            ExCommand::Earlier(span) => self
+                .view_mut()
+                .handle_command(EditorCommand::Normal(NormalCommand::Earlier(span))),
+            ExCommand::Later(span) => self
+                .view_mut()
+                .handle_command(EditorCommand::Normal(NormalCommand::Later(span))),
+        }
+        self.refresh_status();
+    }
+
+    fn set_options(&mut self, arguments: &[String]) {
+        if arguments.is_empty() {
+            self.message_bar
+                .update_message(String::from("Argument required"));
+            return;
+        }
+        let mut messages = Vec::new();
+        for argument in arguments {
+            let result = Self::tab_mut(&mut self.tabs, self.tab)
+                .active_mut()
+                .set_buffer_option(argument)
+                .unwrap_or_else(|| self.options.set(argument));
+            match result {
+                Ok(message) => messages.extend(message),
+                Err(err) => {
+                    self.message_bar.update_message(err);
+                    return;
+                }
+            }
+        }
+        if !messages.is_empty() {
+            self.message_bar.update_message(messages.join("  "));
+        }
+    }
+
+    fn handle_window_command(&mut self, command: WindowCommand) {
+        match command {
+            WindowCommand::Split => self.split(SplitDirection::Horizontal, None),
+            WindowCommand::VerticalSplit => self.split(SplitDirection::Vertical, None),
+            WindowCommand::Focus(direction) => self.layout_mut().focus(direction),
+            WindowCommand::Next => self.layout_mut().focus_next(),
+            WindowCommand::Close => self.close_window(false),
+            WindowCommand::Only => self.layout_mut().only(),
+            WindowCommand::Equalize => self.layout_mut().equalize(),
+        }
+        self.refresh_status();
+    }
+
+    /// Splits the window, editing `file_name` in the new one if given
+    fn split(&mut self, direction: SplitDirection, file_name: Option<&str>) {
+        if let Err(err) = self.layout_mut().split(direction) {
+            self.message_bar.update_message(err);
+            return;
+        }
+        if let Some(file_name) = file_name {
+            self.open(file_name);
+        }
+    }
+
+    /// Closes the window, keeping its place in the buffer for later. The
+    /// last window showing a modified buffer needs `force`.
+    fn close_window(&mut self, force: bool) {
+        if self.layout().window_count() > 1 {
+            let active: *const View = self.view();
+            if !self.may_close_windows(force, |_, view| ptr::eq(view, active)) {
+                return;
+            }
+        }
+        let current = self.current_buffer();
+        self.buffers.save_state(current, self.view().state());
+        if let Err(err) = self.layout_mut().close() {
+            self.message_bar.update_message(err);
+        }
+    }
+
+    /// `:q` closes the window, or the tab page with its last window, and
+    /// quits when it is the last window of all
+    fn quit_window(&mut self, force: bool) {
+        if self.layout().window_count() > 1 {
+            self.close_window(force);
+        } else if self.tabs.len() > 1 {
+            self.close_tab(force);
+        } else if self.may_quit(force) {
+            self.should_quit = true;
+        }
+    }
+
+    /// Opens a tab page after the current one, editing `file_name` if given
+    /// or else a new empty buffer
+    fn new_tab(&mut self, file_name: Option<&str>) {
+        let current = self.current_buffer();
+        self.buffers.save_state(current, self.view().state());
+
+        let layout = Layout::default();
+        if file_name.is_none() {
+            self.buffers.push(Rc::clone(layout.active().buffer()));
+        }
+        self.tab = self.tab.saturating_add(1);
+        self.tabs.insert(self.tab, layout);
+        self.resize(self.terminal_size);
+        if let Some(file_name) = file_name {
+            self.open(file_name);
+        }
+        self.update_tab_line();
+    }
+
+    /// Goes to the tab page with the 1-based `number`, or to the next one,
+    /// wrapping around after the last
+    #[allow(clippy::arithmetic_side_effects)]
+    fn next_tab(&mut self, number: Option<usize>) {
+        let Some(number) = number else {
+            self.switch_to_tab((self.tab + 1) % self.tabs.len());
+            return;
+        };
+        match number
+            .checked_sub(1)
+            .filter(|&index| index < self.tabs.len())
+        {
+            Some(index) => self.switch_to_tab(index),
+            None => self
+                .message_bar
+                .update_message(format!("Tab page {number} does not exist")),
+        }
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn previous_tab(&mut self) {
+        let count = self.tabs.len();
+        self.switch_to_tab((self.tab + count - 1) % count);
+    }
+
+    fn switch_to_tab(&mut self, index: usize) {
+        if index == self.tab || index >= self.tabs.len() {
+            return;
+        }
+        let current = self.current_buffer();
+        self.buffers.save_state(current, self.view().state());
+        self.tab = index;
+        self.layout_mut().set_needs_redraw();
+        self.update_tab_line();
+        self.refresh_status();
+    }
+
+    /// Closes the tab page with all its windows. The last one can't be
+    /// closed, and modified buffers not shown in other tab pages need `force`.
+    fn close_tab(&mut self, force: bool) {
+        if self.tabs.len() <= 1 {
+            self.message_bar
+                .update_message(String::from("Cannot close last tab page"));
+            return;
+        }
+        let tab = self.tab;
+        if !self.may_close_windows(force, |index, _| index == tab) {
+            return;
+        }
+        let current = self.current_buffer();
+        self.buffers.save_state(current, self.view().state());
+        self.tabs.remove(self.tab);
+        self.tab = self.tab.min(self.tabs.len().saturating_sub(1));
+        self.resize(self.terminal_size);
+        self.layout_mut().set_needs_redraw();
+        self.update_tab_line();
+    }
+
+    /// Labels each tab page with the buffer of its active window
+    fn update_tab_line(&mut self) {
+        let labels = self
+            .tabs
+            .iter()
+            .map(|layout| {
+                let status = layout.active().get_status();
+                if status.is_modified {
+                    format!("+ {}", status.file_name)
+                } else {
+                    status.file_name
+                }
+            })
+            .collect();
+        self.tab_line.update_tabs(labels, self.tab);
+    }
+
+    /// Quitting with modified buffers requires pressing Ctrl-Q `QUIT_TIMES` times in a row
+    #[allow(clippy::arithmetic_side_effects)]
+    fn handle_quit(&mut self) {
+        if self.buffers.first_modified().is_none() || self.quit_times + 1 == QUIT_TIMES {
+            self.should_quit = true;
+        } else {
+            self.message_bar.update_message(format!(
+                "WARNING! File has unsaved changes. Press Ctrl-Q {} more times to quit.",
+                QUIT_TIMES - self.quit_times - 1
+            ));
+            self.quit_times += 1;
+        }
+    }
+
+    fn reset_quit_times(&mut self) {
+        if self.quit_times > 0 {
+            self.quit_times = 0;
+            self.message_bar.update_message(String::new());
+        }
+    }
+
+    /// Checks whether the buffer may be closed, warning in the message bar if not
+    fn may_discard_changes(&mut self, force: bool) -> bool {
+        if force || !self.view().is_modified() {
+            return true;
+        }
+        self.message_bar.update_message(String::from(
+            "No write since last change (add ! to override)",
+        ));
+        false
+    }
+
+    /// Checks whether the windows for which `closing` holds, given the index
+    /// of their tab page, may be closed. Without `force` no modified buffer
+    /// may lose its last window. Warns in the message bar if not.
+    fn may_close_windows(&mut self, force: bool, closing: impl Fn(usize, &View) -> bool) -> bool {
+        if force {
+            return true;
+        }
+        let windows = || {
+            self.tabs
+                .iter()
+                .enumerate()
+                .flat_map(|(index, layout)| layout.windows().map(move |view| (index, view)))
+        };
+        let unsaved = windows()
+            .filter(|&(index, view)| closing(index, view) && view.is_modified())
+            .any(|(_, closed)| {
+                !windows().any(|(index, view)| {
+                    !closing(index, view) && Rc::ptr_eq(view.buffer(), closed.buffer())
+                })
+            });
+        if unsaved {
+            self.message_bar.update_message(String::from(
+                "No write since last change (add ! to override)",
+            ));
+        }
+        !unsaved
+    }
+
+    /// Checks whether all buffers may be closed, warning in the message bar if not
+    fn may_quit(&mut self, force: bool) -> bool {
+        if !self.may_discard_changes(force) {
+            return false;
+        }
+        match self.buffers.first_modified() {
+            Some(index) if !force => {
+                let message = self
+                    .buffers
+                    .get(index)
+                    .map_or_else(String::new, |(buffer, _)| {
+                        format!(
+                            "No write since last change for buffer {} \"{}\" (add ! to override)",
+                            index.saturating_add(1),
+                            buffer.borrow().file_info
+                        )
+                    });
+                self.message_bar.update_message(message);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Saves the buffer, asking for a file name when it does not have one yet
+    fn save(&mut self) {
+        if self.view().file_name().is_some() {
+            self.write(None, false);
+        } else {
+            self.start_prompt(PromptType::SaveAs);
+        }
+    }
+
+    /// Index of the buffer shown in the view
+    fn current_buffer(&self) -> usize {
+        self.buffers
+            .index_of(self.view().buffer())
+            .unwrap_or_default()
+    }
+
+    /// Shows the buffer at `index`, where it was left the last time
+    fn switch_to_buffer(&mut self, index: usize) {
+        let Some((buffer, state)) = self.buffers.get(index) else {
+            self.message_bar
+                .update_message(format!("Buffer {} does not exist", index.saturating_add(1)));
+            return;
+        };
+        let buffer = Rc::clone(buffer);
+        let current = self.current_buffer();
+        self.buffers.save_state(current, self.view().state());
+        self.view_mut().set_buffer(buffer, state);
+        self.refresh_status();
+    }
+
+    /// Goes to the next or previous buffer, wrapping around at the ends
+    #[allow(clippy::arithmetic_side_effects)]
+    fn cycle_buffer(&mut self, forward: bool) {
+        let count = self.buffers.len();
+        if count == 0 {
+            return;
+        }
+        let current = self.current_buffer();
+        let index = if forward {
+            (current + 1) % count
+        } else {
+            (current + count - 1) % count
+        };
+        self.view_mut().record_jump();
+        self.switch_to_buffer(index);
+    }
+
+    /// Finds a buffer by its number or by part of its name
+    fn find_buffer(&self, argument: &str) -> Result<usize, String> {
+        match argument.parse::<usize>() {
+            Ok(number) => number
+                .checked_sub(1)
+                .filter(|&index| index < self.buffers.len())
+                .ok_or_else(|| format!("Buffer {number} does not exist")),
+            Err(_) => self.buffers.find_name(argument),
+        }
+    }
+
+    /// One entry per buffer: its number, `%` for the current one, `+` if
+    /// modified, its name and the line the cursor is on
+    fn list_buffers(&self) -> String {
+        let current = self.current_buffer();
+        self.buffers
+            .iter()
+            .enumerate()
+            .map(|(index, (buffer, state))| {
+                let buffer = buffer.borrow();
+                let line_index = if index == current {
+                    self.view().get_status().current_line_index
+                } else {
+                    state.line_index()
+                };
+                format!(
+                    "{} {}{} \"{}\" line {}",
+                    index.saturating_add(1),
+                    if index == current { '%' } else { ' ' },
+                    if buffer.is_dirty() { '+' } else { ' ' },
+                    buffer.file_info,
+                    line_index.saturating_add(1),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// Switches to the buffer of `file_name`, opening it first if needed
+    fn open(&mut self, file_name: &str) {
+        match self.add_file(file_name) {
+            Ok((index, message)) => {
+                self.view_mut().record_jump();
+                self.switch_to_buffer(index);
+                self.message_bar.update_message(message);
+            }
+            Err(err) => self.message_bar.update_message(err),
+        }
+    }
+
+    /// Loads `file_name` into a new buffer, or starts a new file of that name
+    /// if it does not exist. Returns the index of the buffer and the message
+    /// to show, or the error if it could not be read. An already open file is
+    /// not loaded again.
+    fn add_file(&mut self, file_name: &str) -> Result<(usize, String), String> {
+        if let Some(index) = self.buffers.find_file(Path::new(file_name)) {
+            let lines = self
+                .buffers
+                .get(index)
+                .map_or(0, |(buffer, _)| buffer.borrow().height());
+            return Ok((index, format!("\"{file_name}\" {lines} lines")));
+        }
+        let (buffer, message) = Self::load(file_name)?;
+
+        let buffer = Rc::new(RefCell::new(buffer));
+        let current = self.current_buffer();
+        let index = if self.is_unused(current) {
+            // Like vim, the empty buffer of a session started without files
+            // is taken over by the first file opened
+            self.buffers.replace(current, Rc::clone(&buffer));
+            self.view_mut().set_buffer(buffer, ViewState::default());
+            current
+        } else {
+            self.buffers.push(buffer)
+        };
+        Ok((index, message))
+    }
+
+    /// A buffer without name, content or changes
+    fn is_unused(&self, index: usize) -> bool {
+        self.buffers.get(index).is_some_and(|(buffer, _)| {
+            let buffer = buffer.borrow();
+            buffer.file_info.path.is_none() && buffer.is_empty() && !buffer.is_dirty()
+        })
+    }
+
+    /// Reads the current file again, dropping any changes
+    fn reload(&mut self) {
+        let Some(file_name) = self.view().file_name() else {
+            self.message_bar
+                .update_message(String::from("No file name"));
+            return;
+        };
+        match Self::load(&file_name) {
+            Ok((buffer, message)) => {
+                let shared = Rc::clone(self.view().buffer());
+                *shared.borrow_mut() = buffer;
+                self.view_mut().set_buffer(shared, ViewState::default());
+                self.message_bar.update_message(message);
+            }
+            Err(message) => self.message_bar.update_message(message),
+        }
+        self.refresh_status();
+    }
+
+    /// Loads `file_name`, or starts a new file of that name if it does not
+    /// exist. Returns the buffer and the message to show.
+    fn load(file_name: &str) -> Result<(Buffer, String), String> {
+        match Buffer::load(file_name) {
+            Ok(buffer) => {
+                let bytes = fs::metadata(file_name).map_or(0, |metadata| metadata.len());
+                let converted = match buffer.file_info.format.encoding {
+                    Encoding::Utf8 => String::new(),
+                    encoding => format!("[{encoding}] "),
+                };
+                let message = format!("\"{file_name}\" {converted}{}L, {bytes}B", buffer.height());
+                Ok((buffer, message))
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok((
+                Buffer::new_file(file_name),
+                format!("\"{file_name}\" [New]"),
+            )),
+            Err(err) => Err(format!("Could not open \"{file_name}\": {err}")),
+        }
+    }
+
+    /// Writes the buffer, to `file` if given. Only with `force` does it
+    /// write over another file that exists. Returns whether it succeeded.
+    fn write(&mut self, file: Option<&str>, force: bool) -> bool {
+        match (file, self.view().file_name()) {
+            (Some(file), Some(_)) => {
+                let file = file.trim();
+                if let Err(err) = self.check_target(file, force) {
+                    self.message_bar.update_message(err);
+                    return false;
+                }
+                let result = self.view().write_to(file, &self.options);
+                self.report_write(file, result)
+            }
+            (Some(file), None) => self.save_as(file, force),
+            (None, Some(name)) => {
+                let result = Self::tab_mut(&mut self.tabs, self.tab)
+                    .active_mut()
+                    .save(&self.options);
+                self.report_write(&name, result)
+            }
+            (None, None) => {
+                self.message_bar
+                    .update_message(String::from("No file name"));
+                false
+            }
+        }
+    }
+
+    /// Saves the buffer under `file_name`, which becomes its name
+    fn save_as(&mut self, file_name: &str, force: bool) -> bool {
+        let file_name = file_name.trim();
+        if let Err(err) = self.check_target(file_name, force) {
+            self.message_bar.update_message(err);
+            return false;
+        }
+
+        let result = Self::tab_mut(&mut self.tabs, self.tab)
+            .active_mut()
+            .save_as(file_name, &self.options);
+        self.refresh_status();
+        self.report_write(file_name, result)
+    }
+
+    /// Whether the buffer may be written to `file_name`, which must not be
+    /// another file that exists unless `force` is given
+    fn check_target(&self, file_name: &str, force: bool) -> Result<(), String> {
+        Self::validate_file_name(file_name)?;
+        let own_file = self
+            .view()
+            .file_name()
+            .is_some_and(|current| Path::new(&current) == Path::new(file_name));
+        if !force && !own_file && Path::new(file_name).exists() {
+            return Err(String::from("File exists (add ! to override)"));
+        }
+        Ok(())
+    }
+
+    fn validate_file_name(file_name: &str) -> Result<(), String> {
+        if file_name.is_empty() {
+            return Err(String::from("No file name"));
+        }
+        let path = Path::new(file_name);
+        if path.is_dir() {
+            return Err(format!("\"{file_name}\" is a directory"));
+        }
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.is_dir()
+        {
+            return Err(format!(
+                "Directory does not exist: {}",
+                parent.to_string_lossy()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Tells how writing the buffer of the active window went. The lines are
+    /// the ones of the buffer, even when its bytes are shown.
+    fn report_write(&mut self, file_name: &str, result: Result<usize, Error>) -> bool {
+        match result {
+            Ok(bytes) => {
+                let lines = self.view().buffer().borrow().height();
+                self.message_bar
+                    .update_message(format!("\"{file_name}\" {lines}L, {bytes}B written"));
+                true
+            }
+            Err(err) => {
+                self.message_bar
+                    .update_message(format!("Could not write \"{file_name}\": {err}"));
+                false
+            }
+        }
+    }
 }
 
 impl Drop for Editor {
@@ -178,3 +1471,42 @@ impl Drop for Editor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> Editor {
+        let mut editor = Editor::default();
+        editor.tabs.push(Layout::default());
+        editor.buffers.push(Rc::clone(editor.view().buffer()));
+        editor
+    }
+
+    fn press(editor: &mut Editor, code: KeyCode, modifiers: KeyModifiers) {
+        editor.evaluate_event(&Event::Key(KeyEvent::new(code, modifiers)));
+    }
+
+    #[test]
+    fn quitting_with_changes_takes_ctrl_q_in_a_row() {
+        let mut editor = editor();
+        for code in [KeyCode::Char('i'), KeyCode::Char('x'), KeyCode::Esc] {
+            press(&mut editor, code, KeyModifiers::NONE);
+        }
+        let ctrl_q = |editor: &mut Editor| press(editor, KeyCode::Char('q'), KeyModifiers::CONTROL);
+
+        ctrl_q(&mut editor);
+        ctrl_q(&mut editor);
+        // A key that only starts a command breaks the row too. The Ctrl-Q
+        // after it doesn't count, it makes `g` fail.
+        press(&mut editor, KeyCode::Char('g'), KeyModifiers::NONE);
+        for _ in 0..3 {
+            ctrl_q(&mut editor);
+        }
+        assert!(!editor.should_quit);
+        ctrl_q(&mut editor);
+        assert!(editor.should_quit);
+        // Dropping it would restore a terminal the test never set up
+        std::mem::forget(editor);
+    }
+}
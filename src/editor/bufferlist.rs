@@ -0,0 +1,110 @@
+use std::{fs, path::Path, rc::Rc};
+
+use super::view::{SharedBuffer, ViewState};
+
+struct Entry {
+    buffer: SharedBuffer,
+    /// Where the buffer was last shown, to return there when switching back
+    state: ViewState,
+}
+
+/// All open buffers, in the order they were opened. Buffers are numbered
+/// from 1 by their position in the list.
+#[derive(Default)]
+pub struct BufferList {
+    entries: Vec<Entry>,
+}
+
+impl BufferList {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<(&SharedBuffer, ViewState)> {
+        self.entries
+            .get(index)
+            .map(|entry| (&entry.buffer, entry.state))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SharedBuffer, ViewState)> {
+        self.entries
+            .iter()
+            .map(|entry| (&entry.buffer, entry.state))
+    }
+
+    /// Adds a buffer at the end and returns its index
+    pub fn push(&mut self, buffer: SharedBuffer) -> usize {
+        self.entries.push(Entry {
+            buffer,
+            state: ViewState::default(),
+        });
+        self.entries.len().saturating_sub(1)
+    }
+
+    /// Puts `buffer` in place of the one at `index`
+    pub fn replace(&mut self, index: usize, buffer: SharedBuffer) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            *entry = Entry {
+                buffer,
+                state: ViewState::default(),
+            };
+        }
+    }
+
+    pub fn index_of(&self, buffer: &SharedBuffer) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| Rc::ptr_eq(&entry.buffer, buffer))
+    }
+
+    /// Finds the buffer of the file at `path`, also when named differently,
+    /// e.g. through a relative path
+    pub fn find_file(&self, path: &Path) -> Option<usize> {
+        let canonical = fs::canonicalize(path).ok();
+        self.entries.iter().position(|entry| {
+            entry
+                .buffer
+                .borrow()
+                .file_info
+                .path
+                .as_ref()
+                .is_some_and(|other| {
+                    other == path
+                        || canonical.as_ref().is_some_and(|canonical| {
+                            fs::canonicalize(other).ok().as_ref() == Some(canonical)
+                        })
+                })
+        })
+    }
+
+    /// Finds the single buffer whose name contains `pattern`
+    pub fn find_name(&self, pattern: &str) -> Result<usize, String> {
+        let mut matches = self.entries.iter().enumerate().filter(|(_, entry)| {
+            entry
+                .buffer
+                .borrow()
+                .file_info
+                .path
+                .as_ref()
+                .is_some_and(|path| path.to_string_lossy().contains(pattern))
+        });
+        match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => Ok(index),
+            (None, _) => Err(format!("No matching buffer for {pattern}")),
+            (Some(_), Some(_)) => Err(format!("More than one match for {pattern}")),
+        }
+    }
+
+    pub fn save_state(&mut self, index: usize, state: ViewState) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.state = state;
+        }
+    }
+
+    /// Index of the first buffer with unsaved changes
+    pub fn first_modified(&self) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.buffer.borrow().is_dirty())
+    }
+}
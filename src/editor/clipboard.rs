@@ -0,0 +1,211 @@
+use std::{
+    env, fmt,
+    io::{Error, ErrorKind, Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::terminal::Terminal;
+
+/// Where the `"+` and `"*` registers are copied to and pasted from, set
+/// with `:set clipboard=`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ClipboardProvider {
+    /// The first tool found for the display server, else the terminal
+    #[default]
+    Auto,
+    /// The terminal, with OSC 52 escape sequences. Nothing can be pasted
+    /// from it, so pasting uses the text the editor kept.
+    Osc52,
+    WlCopy,
+    Xclip,
+    Xsel,
+    /// The registers stay within the editor
+    None,
+}
+
+impl ClipboardProvider {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "auto" => Ok(Self::Auto),
+            "osc52" => Ok(Self::Osc52),
+            "wl-copy" => Ok(Self::WlCopy),
+            "xclip" => Ok(Self::Xclip),
+            "xsel" => Ok(Self::Xsel),
+            "none" => Ok(Self::None),
+            _ => Err(format!("Invalid argument: clipboard={value}")),
+        }
+    }
+
+    /// The tool to run, `None` for the terminal or the editor alone
+    fn tool(self) -> Option<Tool> {
+        match self {
+            Self::Auto => Tool::detect(),
+            Self::WlCopy => Some(Tool::WlCopy),
+            Self::Xclip => Some(Tool::Xclip),
+            Self::Xsel => Some(Tool::Xsel),
+            Self::Osc52 | Self::None => None,
+        }
+    }
+}
+
+impl fmt::Display for ClipboardProvider {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Auto => "auto",
+            Self::Osc52 => "osc52",
+            Self::WlCopy => "wl-copy",
+            Self::Xclip => "xclip",
+            Self::Xsel => "xsel",
+            Self::None => "none",
+        };
+        write!(formatter, "{name}")
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Tool {
+    WlCopy,
+    Xclip,
+    Xsel,
+}
+
+impl Tool {
+    /// `wl-copy` under Wayland, `xclip` or `xsel` under X, if installed
+    fn detect() -> Option<Self> {
+        let wayland = env::var_os("WAYLAND_DISPLAY").is_some();
+        let x = env::var_os("DISPLAY").is_some();
+        [(Self::WlCopy, wayland), (Self::Xclip, x), (Self::Xsel, x)]
+            .into_iter()
+            .find(|&(tool, display)| display && is_installed(tool.program(false)))
+            .map(|(tool, _)| tool)
+    }
+
+    const fn program(self, paste: bool) -> &'static str {
+        match self {
+            Self::WlCopy if paste => "wl-paste",
+            Self::WlCopy => "wl-copy",
+            Self::Xclip => "xclip",
+            Self::Xsel => "xsel",
+        }
+    }
+
+    /// The command copying from its input, or pasting to its output
+    fn command(self, primary: bool, paste: bool) -> Command {
+        let mut command = Command::new(self.program(paste));
+        match self {
+            Self::WlCopy => {
+                if primary {
+                    command.arg("--primary");
+                }
+                if paste {
+                    command.arg("--no-newline");
+                }
+            }
+            Self::Xclip => {
+                let selection = if primary { "primary" } else { "clipboard" };
+                command.args(["-selection", selection]);
+                if paste {
+                    command.arg("-o");
+                }
+            }
+            Self::Xsel => {
+                command.arg(if primary { "--primary" } else { "--clipboard" });
+                command.arg(if paste { "--output" } else { "--input" });
+            }
+        }
+        command.stderr(Stdio::null());
+        command
+    }
+
+    fn copy(self, text: &str, primary: bool) -> Result<(), Error> {
+        let mut child = self
+            .command(primary, false)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        // Written from another thread, a tool that doesn't read can't block
+        let writer = child.stdin.take().map(|mut input| {
+            let text = text.to_string();
+            thread::spawn(move || input.write_all(text.as_bytes()))
+        });
+        let status = wait(&mut child)?;
+        if let Some(writer) = writer {
+            writer
+                .join()
+                .map_err(|_| Error::other("writing panicked"))??;
+        }
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::other(status.to_string()))
+        }
+    }
+
+    fn paste(self, primary: bool) -> Option<String> {
+        let mut child = self
+            .command(primary, true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut output = child.stdout.take()?;
+        // Read from another thread, the tool can't block on a full pipe
+        let reader = thread::spawn(move || {
+            let mut text = Vec::new();
+            output.read_to_end(&mut text).map(|_| text)
+        });
+        let status = wait(&mut child).ok()?;
+        let text = reader.join().ok()?.ok()?;
+        status
+            .success()
+            .then(|| String::from_utf8_lossy(&text).into_owned())
+    }
+}
+
+/// How long a clipboard tool may run, so that a hung one can't freeze the
+/// editor
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Waits for `child` to exit, killing it once it ran for `TIMEOUT`
+fn wait(child: &mut Child) -> Result<ExitStatus, Error> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if started.elapsed() >= TIMEOUT {
+            child.kill()?;
+            child.wait()?;
+            return Err(Error::new(ErrorKind::TimedOut, "timed out"));
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// Whether `program` is in one of the `PATH` directories
+fn is_installed(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Copies `text` to the clipboard for `"+`, or to the primary selection
+/// for `"*`
+pub fn copy(provider: ClipboardProvider, name: char, text: &str) -> Result<(), String> {
+    let primary = name == '*';
+    match provider.tool() {
+        Some(tool) => tool
+            .copy(text, primary)
+            .map_err(|err| format!("Cannot copy with {}: {err}", tool.program(false))),
+        None if provider == ClipboardProvider::None => Ok(()),
+        None => Terminal::copy_to_clipboard(text, primary)
+            .map_err(|err| format!("Cannot copy to the clipboard: {err}")),
+    }
+}
+
+/// The text of the clipboard for `"+`, or of the primary selection for
+/// `"*`. `None` if there is no tool to paste with or it failed.
+pub fn paste(provider: ClipboardProvider, name: char) -> Option<String> {
+    provider.tool()?.paste(name == '*')
+}
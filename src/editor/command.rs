@@ -1,18 +1,33 @@
+use std::time::Duration;
+
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 
-use super::terminal::Size;
+use super::{register::Registers, terminal::Size};
+
+mod excommand;
+mod lastchange;
+mod macrorecorder;
+mod pendingkeys;
+
+pub use excommand::ExCommand;
+pub use lastchange::LastChange;
+pub use macrorecorder::{MacroRecorder, keys_from_text};
+pub use pendingkeys::{Counted, PendingKeys};
+
+/// Counts above this are cut down, so that a mistyped count can't keep
+/// the editor busy
+const MAX_COUNT: usize = 99_999;
+
+/// The count and register typed before a command, and the keys after them
+type CommandPrefix<'a> = (Option<usize>, Option<char>, &'a [KeyEvent]);
 
-#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
 pub enum Mode {
+    #[default]
     Normal,
     Insert,
     Visual,
-}
-
-impl Default for Mode {
-    fn default() -> Self {
-        Self::Normal
-    }
+    Command,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -27,9 +42,193 @@ pub enum Direction {
     Down,
 }
 
+/// Whether a word is a run of letters or of other non-blank characters
+/// (`w`), or anything between blanks (`W`)
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WordKind {
+    Word,
+    BigWord,
+}
+
+/// Cursor motions beyond the arrow keys
+#[derive(Debug, Clone, Copy)]
+pub enum Motion {
+    /// `w`, `W`
+    WordStart(WordKind),
+    /// `b`, `B`
+    PreviousWordStart(WordKind),
+    /// `e`, `E`
+    WordEnd(WordKind),
+    /// `ge`, `gE`
+    PreviousWordEnd(WordKind),
+    /// `}`
+    NextParagraph,
+    /// `{`
+    PreviousParagraph,
+    /// `0`
+    LineStart,
+    /// `^`
+    FirstNonBlank,
+    /// `$`
+    LineEnd,
+    /// `gg`
+    FirstLine,
+    /// `G`
+    LastLine,
+    /// `h` after an operator
+    Left,
+    /// `l` after an operator
+    Right,
+    /// `k` after an operator
+    Up,
+    /// `j` after an operator
+    Down,
+    /// `` `a ``, or `'a` to the first non-blank of the line
+    Mark { name: char, exact: bool },
+}
+
+impl Motion {
+    /// Whether an operator acts on whole lines, e.g. `dj`
+    pub const fn is_linewise(self) -> bool {
+        matches!(
+            self,
+            Self::FirstLine
+                | Self::LastLine
+                | Self::Up
+                | Self::Down
+                | Self::Mark { exact: false, .. }
+        )
+    }
+
+    /// Whether the motion goes to the jump list, so that Ctrl-O comes back
+    pub const fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Self::FirstLine
+                | Self::LastLine
+                | Self::NextParagraph
+                | Self::PreviousParagraph
+                | Self::Mark { .. }
+        )
+    }
+
+    /// Whether an operator includes the character the motion ends on, e.g. `de`
+    pub const fn is_inclusive(self) -> bool {
+        matches!(self, Self::WordEnd(_) | Self::PreviousWordEnd(_))
+    }
+
+    fn from_direction(direction: Direction) -> Option<Self> {
+        match direction {
+            Direction::Left => Some(Self::Left),
+            Direction::Right => Some(Self::Right),
+            Direction::Up => Some(Self::Up),
+            Direction::Down => Some(Self::Down),
+            Direction::Home => Some(Self::LineStart),
+            Direction::End => Some(Self::LineEnd),
+            Direction::PageUp | Direction::PageDown => None,
+        }
+    }
+}
+
+/// Commands that act on the text a motion moves over
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Operator {
+    /// `d`
+    Delete,
+    /// `c`
+    Change,
+    /// `y`
+    Yank,
+    /// `>`
+    Indent,
+    /// `<`
+    Outdent,
+    /// `gu`
+    Lowercase,
+    /// `gU`
+    Uppercase,
+    /// `g~`
+    ToggleCase,
+}
+
+/// Text objects, which select the text around the cursor instead of moving it
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TextObject {
+    /// `w`, `W`
+    Word(WordKind),
+    /// `s`
+    Sentence,
+    /// `p`
+    Paragraph,
+    /// The text between a pair of quotes on the line, `"`, `'` or `` ` ``
+    Quote(char),
+    /// The text between a pair of brackets, given by the opening one
+    Bracket(char),
+    /// The text between an XML or HTML tag and its closing tag
+    Tag,
+}
+
+impl TextObject {
+    fn from_key(code: KeyCode) -> Option<Self> {
+        let object = match code {
+            KeyCode::Char('w') => Self::Word(WordKind::Word),
+            KeyCode::Char('W') => Self::Word(WordKind::BigWord),
+            KeyCode::Char('s') => Self::Sentence,
+            KeyCode::Char('p') => Self::Paragraph,
+            KeyCode::Char(quote @ ('"' | '\'' | '`')) => Self::Quote(quote),
+            KeyCode::Char('(' | ')' | 'b') => Self::Bracket('('),
+            KeyCode::Char('[' | ']') => Self::Bracket('['),
+            KeyCode::Char('{' | '}' | 'B') => Self::Bracket('{'),
+            KeyCode::Char('<' | '>') => Self::Bracket('<'),
+            KeyCode::Char('t') => Self::Tag,
+            _ => return None,
+        };
+        Some(object)
+    }
+}
+
+/// What an operator acts on
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    Motion(Motion),
+    /// The current line and the ones below it, for a doubled operator like `dd`
+    Lines,
+    /// A text object with (`a`) or without (`i`) what surrounds it, like
+    /// the white space after a word or the brackets of a block
+    Object {
+        object: TextObject,
+        around: bool,
+    },
+}
+
+/// How far to travel through the undo tree
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeSpan {
+    Steps(usize),
+    Duration(Duration),
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum NormalCommand {
     Move(Direction),
+    Motion(Motion),
+    Operate(Operator, Target),
+    /// `p`, or `P` to put the text before the cursor
+    Put {
+        before: bool,
+    },
+    Undo,
+    Redo,
+    Earlier(TimeSpan),
+    Later(TimeSpan),
+    /// `.`, makes the last change again
+    Repeat,
+    /// `m{mark}`
+    SetMark(char),
+    /// Ctrl-O, back to where a jump came from
+    OlderJump,
+    /// Ctrl-I or Tab, forth again after Ctrl-O
+    NewerJump,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -40,9 +239,75 @@ pub enum InsertCommand {
     Enter,
 }
 
+/// Line editing on the `:` command line
+#[derive(Debug, Clone, Copy)]
+pub enum CommandLineCommand {
+    Char(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Submit,
+}
+
+/// `Ctrl-W` commands acting on the split windows
+#[derive(Debug, Clone, Copy)]
+pub enum WindowCommand {
+    Split,
+    VerticalSplit,
+    /// Go to the window in the direction
+    Focus(Direction),
+    Next,
+    Close,
+    Only,
+    Equalize,
+}
+
+/// Moving between tab pages
+#[derive(Debug, Clone, Copy)]
+pub enum TabCommand {
+    Next,
+    Previous,
+}
+
+/// How Visual mode selects the text between where it started and the cursor
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VisualKind {
+    /// `v`
+    Char,
+    /// `V`, whole lines
+    Line,
+    /// Ctrl-V, the columns between the two ends on each line
+    Block,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum VisualCommand {
-    None,
+    /// Starts Visual mode, switches to another kind of selection, or leaves
+    /// Visual mode when it is the same kind
+    Select(VisualKind),
+    /// `o`, goes to the other end of the selection
+    SwapEnds,
+    /// `d`, `y`, `c`, `>`, `<`, `~`, `u`, `U` on the selection
+    Operate(Operator),
+    /// `J`
+    Join,
+    /// `r`, replaces every character of the selection
+    Replace(char),
+}
+
+/// Recording keys into a register and typing them again
+#[derive(Debug, Clone, Copy)]
+pub enum MacroCommand {
+    /// `q{register}`
+    Record(char),
+    /// `q` while recording
+    Stop,
+    /// `@{register}`, with `@@` the register played last and `@:` the last
+    /// command line
+    Play(char),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -50,9 +315,14 @@ pub enum EditorCommand {
     Normal(NormalCommand),
     Insert(InsertCommand),
     Visual(VisualCommand),
+    CommandLine(CommandLineCommand),
+    Window(WindowCommand),
+    Tab(TabCommand),
+    Macro(MacroCommand),
 
     // Global events through all the modes
     Resize(Size),
+    ToggleUndoTree,
     Change(Mode),
     Save,
     Esc,
@@ -61,61 +331,388 @@ pub enum EditorCommand {
 
 impl EditorCommand {
     #[allow(clippy::as_conversions)]
-    pub fn try_from(event: Event, mode: &Mode) -> Result<Self, String> {
+    pub fn try_from(event: &Event, mode: Mode) -> Result<Self, String> {
         // println!("Command: {event:?}, mode: {mode:?}");
-        match (&event, &mode) {
+        match (event, mode) {
             (Event::Resize(width_u16, height_u16), _) => {
-                let height = height_u16.clone() as usize;
-                let width = width_u16.clone() as usize;
+                let height = *height_u16 as usize;
+                let width = *width_u16 as usize;
 
                 Ok(Self::Resize(Size { height, width }))
             }
             (Event::Key(KeyEvent { code, .. }), _) if code.is_esc() => Ok(Self::Esc),
-            (_, Mode::Normal) => Self::from_normal_command(event),
-            (_, Mode::Visual) => Self::from_visual_command(event),
+            (Event::Key(key), Mode::Normal) => Self::from_normal_keys(&[*key])?
+                .map(|counted| counted.command)
+                .ok_or_else(|| format!("Incomplete command: {:?}", key.code)),
+            (Event::Key(key), Mode::Visual) => Self::from_visual_keys(&[*key])?
+                .map(|counted| counted.command)
+                .ok_or_else(|| format!("Incomplete command: {:?}", key.code)),
+            (_, Mode::Normal | Mode::Visual) => {
+                Err(format!("Event not processed for normal command: {event:?}"))
+            }
             (_, Mode::Insert) => Self::from_insert_command(event),
+            (_, Mode::Command) => Self::from_command_line_command(event),
         }
     }
 
-    fn from_normal_command(event: Event) -> Result<Self, String> {
-        if let Event::Key(KeyEvent {
-            code, modifiers, ..
-        }) = event
+    /// Builds a Normal mode command from the keys typed so far, like
+    /// `[count] ["x] command` or `[count] ["x] operator [count] motion`.
+    /// Returns `None` while more keys are needed, e.g. after `g` or `d`.
+    pub fn from_normal_keys(keys: &[KeyEvent]) -> Result<Option<Counted>, String> {
+        let Some((count, register, keys)) = Self::split_count_and_register(keys)? else {
+            return Ok(None);
+        };
+        let command = match Self::operator_of(keys) {
+            Some((operator, length)) => {
+                let (operator_keys, rest) = keys.split_at(length);
+                let (motion_count, rest) = Self::split_count(rest);
+                // `2d3w` deletes 6 words
+                let count = Self::multiply_counts(count, motion_count);
+                Self::from_operator_keys(operator, operator_keys, rest)?
+                    .map(|command| Counted::new(command, count, register))
+            }
+            None => {
+                Self::from_command_keys(keys)?.map(|command| Counted::new(command, count, register))
+            }
+        };
+        Ok(command)
+    }
+
+    /// Splits off a count and a register like `"a`, which may also come the
+    /// other way around or both have a count. `None` while the register name
+    /// is missing.
+    fn split_count_and_register(keys: &[KeyEvent]) -> Result<Option<CommandPrefix<'_>>, String> {
+        let (count, keys) = Self::split_count(keys);
+        let (register, keys) = match keys {
+            [quote, rest @ ..] if quote.code == KeyCode::Char('"') => match rest {
+                [] => return Ok(None),
+                [name, rest @ ..] => match name.code {
+                    KeyCode::Char(name) if Registers::is_valid(name) => (Some(name), rest),
+                    code => return Err(format!("Invalid register name: {code:?}")),
+                },
+            },
+            _ => (None, keys),
+        };
+        let (register_count, keys) = Self::split_count(keys);
+        Ok(Some((
+            Self::multiply_counts(count, register_count),
+            register,
+            keys,
+        )))
+    }
+
+    fn multiply_counts(first: Option<usize>, second: Option<usize>) -> Option<usize> {
+        match (first, second) {
+            (Some(first), Some(second)) => Some(first.saturating_mul(second).min(MAX_COUNT)),
+            (first, second) => first.or(second),
+        }
+    }
+
+    /// Builds a Visual mode command from the keys typed so far. Besides the
+    /// commands on the selection, only the motions are taken, which move the
+    /// cursor end of the selection.
+    pub fn from_visual_keys(keys: &[KeyEvent]) -> Result<Option<Counted>, String> {
+        let Some((count, register, keys)) = Self::split_count_and_register(keys)? else {
+            return Ok(None);
+        };
+        let command = match keys {
+            [key] if key.code == KeyCode::Char('r') => None,
+            [key, replacement] if key.code == KeyCode::Char('r') => match replacement.code {
+                KeyCode::Char(ch) => Some(Self::Visual(VisualCommand::Replace(ch))),
+                KeyCode::Tab => Some(Self::Visual(VisualCommand::Replace('\t'))),
+                code => return Err(format!("Not a character to replace with: {code:?}")),
+            },
+            [key] => match Self::from_visual_command(key) {
+                Some(command) => Some(Self::Visual(command)),
+                None => Self::from_visual_motion(keys)?,
+            },
+            _ => Self::from_visual_motion(keys)?,
+        };
+        Ok(command.map(|command| Counted::new(command, count, register)))
+    }
+
+    fn from_visual_motion(keys: &[KeyEvent]) -> Result<Option<Self>, String> {
+        match Self::from_command_keys(keys)? {
+            None => Ok(None),
+            Some(command @ Self::Normal(NormalCommand::Move(_) | NormalCommand::Motion(_))) => {
+                Ok(Some(command))
+            }
+            Some(_) => Err(String::from("Not a Visual mode command")),
+        }
+    }
+
+    /// A command of one key, or two keys with a prefix
+    fn from_command_keys(keys: &[KeyEvent]) -> Result<Option<Self>, String> {
+        match keys {
+            [] => Ok(None),
+            [key] if Self::prefix_of(key).is_some() => Ok(None),
+            [key] => Self::from_normal_command(key).map(Some),
+            [prefix, key] => Self::from_prefixed_normal_command(prefix, key).map(Some),
+            _ => Err(String::from("Too many keys")),
+        }
+    }
+
+    /// Splits the digits of a count off the front of `keys`. A `0` can't
+    /// start a count, on its own it is a motion.
+    fn split_count(keys: &[KeyEvent]) -> (Option<usize>, &[KeyEvent]) {
+        let digits: Vec<usize> = keys
+            .iter()
+            .map_while(|key| match (key.code, key.modifiers) {
+                (KeyCode::Char(ch), KeyModifiers::NONE) => ch.to_digit(10),
+                _ => None,
+            })
+            .enumerate()
+            .take_while(|&(index, digit)| index > 0 || digit > 0)
+            .filter_map(|(_, digit)| usize::try_from(digit).ok())
+            .collect();
+        let count = digits.iter().fold(None, |count: Option<usize>, &digit| {
+            Some(
+                count
+                    .unwrap_or_default()
+                    .saturating_mul(10)
+                    .saturating_add(digit)
+                    .min(MAX_COUNT),
+            )
+        });
+        (count, keys.get(digits.len()..).unwrap_or_default())
+    }
+
+    /// The operator at the start of `keys` and how many keys it takes
+    fn operator_of(keys: &[KeyEvent]) -> Option<(Operator, usize)> {
+        let first = keys.first()?;
+        if first.modifiers.contains(KeyModifiers::CONTROL) {
+            return None;
+        }
+        let operator = match first.code {
+            KeyCode::Char('d') => Operator::Delete,
+            KeyCode::Char('c') => Operator::Change,
+            KeyCode::Char('y') => Operator::Yank,
+            KeyCode::Char('>') => Operator::Indent,
+            KeyCode::Char('<') => Operator::Outdent,
+            KeyCode::Char('g') => {
+                let operator = match keys.get(1)?.code {
+                    KeyCode::Char('u') => Operator::Lowercase,
+                    KeyCode::Char('U') => Operator::Uppercase,
+                    KeyCode::Char('~') => Operator::ToggleCase,
+                    _ => return None,
+                };
+                return Some((operator, 2));
+            }
+            _ => return None,
+        };
+        Some((operator, 1))
+    }
+
+    /// The motion after an operator, or the operator again for whole lines,
+    /// like `dd` or `gUU` and `gUgU`
+    fn from_operator_keys(
+        operator: Operator,
+        operator_keys: &[KeyEvent],
+        keys: &[KeyEvent],
+    ) -> Result<Option<Self>, String> {
+        let doubled = match (keys, operator_keys.last()) {
+            ([key], Some(last)) => key.code == last.code,
+            _ => {
+                keys.len() == operator_keys.len()
+                    && keys
+                        .iter()
+                        .zip(operator_keys)
+                        .all(|(key, operator_key)| key.code == operator_key.code)
+            }
+        };
+        if doubled {
+            return Ok(Some(Self::Normal(NormalCommand::Operate(
+                operator,
+                Target::Lines,
+            ))));
+        }
+
+        if let Some(first) = keys.first()
+            && let KeyCode::Char(selector @ ('i' | 'a')) = first.code
         {
-            match (code, modifiers) {
-                (KeyCode::Char('i'), _) => Ok(Self::Change(Mode::Insert)),
-                (KeyCode::Char('v'), _) => Ok(Self::Change(Mode::Visual)),
-                (KeyCode::Char('q'), KeyModifiers::CONTROL) => Ok(Self::Quit),
-                (KeyCode::Char('s'), KeyModifiers::CONTROL) => Ok(Self::Save),
-                (KeyCode::Up, _) | (KeyCode::Char('k'), _) => {
-                    Ok(Self::Normal(NormalCommand::Move(Direction::Up)))
-                }
-                (KeyCode::Down, _) | (KeyCode::Char('j'), _) => {
-                    Ok(Self::Normal(NormalCommand::Move(Direction::Down)))
-                }
-                (KeyCode::Right, _) | (KeyCode::Char('l'), _) => {
-                    Ok(Self::Normal(NormalCommand::Move(Direction::Right)))
-                }
-                (KeyCode::Left, _) | (KeyCode::Char('h'), _) => {
-                    Ok(Self::Normal(NormalCommand::Move(Direction::Left)))
-                }
-                (KeyCode::PageDown, _) => {
-                    Ok(Self::Normal(NormalCommand::Move(Direction::PageDown)))
-                }
-                (KeyCode::PageUp, _) => Ok(Self::Normal(NormalCommand::Move(Direction::PageUp))),
-                (KeyCode::Home, _) => Ok(Self::Normal(NormalCommand::Move(Direction::Home))),
-                (KeyCode::End, _) => Ok(Self::Normal(NormalCommand::Move(Direction::End))),
-                _ => Err(format!("Key Code not supported: {code:?}")),
+            return match keys {
+                [_] => Ok(None),
+                [_, key] => TextObject::from_key(key.code)
+                    .map(|object| {
+                        Some(Self::Normal(NormalCommand::Operate(
+                            operator,
+                            Target::Object {
+                                object,
+                                around: selector == 'a',
+                            },
+                        )))
+                    })
+                    .ok_or_else(|| format!("Not a text object: {:?}", key.code)),
+                _ => Err(String::from("Too many keys")),
+            };
+        }
+
+        let motion = match Self::from_command_keys(keys)? {
+            None => return Ok(None),
+            Some(Self::Normal(NormalCommand::Motion(motion))) => Some(motion),
+            Some(Self::Normal(NormalCommand::Move(direction))) => Motion::from_direction(direction),
+            Some(_) => None,
+        };
+        motion
+            .map(|motion| {
+                Some(Self::Normal(NormalCommand::Operate(
+                    operator,
+                    Target::Motion(motion),
+                )))
+            })
+            .ok_or_else(|| String::from("Not a motion"))
+    }
+
+    /// First keys of multi-key commands: `g`, `q` and `@` before a register,
+    /// `m`, `'` and `` ` `` before a mark, and Ctrl-W for the window commands
+    fn prefix_of(key: &KeyEvent) -> Option<char> {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char(prefix @ ('g' | 'q' | '@' | 'm' | '\'' | '`')), KeyModifiers::NONE) => {
+                Some(prefix)
             }
-        } else {
-            Err(format!("Event not processed for normal command: {event:?}"))
+            (KeyCode::Char('w'), KeyModifiers::CONTROL) => Some('w'),
+            _ => None,
+        }
+    }
+
+    fn from_normal_command(key: &KeyEvent) -> Result<Self, String> {
+        let KeyEvent {
+            code, modifiers, ..
+        } = *key;
+        match (code, modifiers) {
+            (KeyCode::Char('o'), KeyModifiers::CONTROL) => {
+                Ok(Self::Normal(NormalCommand::OlderJump))
+            }
+            // Terminals send Ctrl-I as Tab
+            (KeyCode::Char('i'), KeyModifiers::CONTROL) | (KeyCode::Tab, _) => {
+                Ok(Self::Normal(NormalCommand::NewerJump))
+            }
+            (KeyCode::Char('i'), _) => Ok(Self::Change(Mode::Insert)),
+            (KeyCode::Char('v'), KeyModifiers::CONTROL) => {
+                Ok(Self::Visual(VisualCommand::Select(VisualKind::Block)))
+            }
+            (KeyCode::Char('v'), _) => Ok(Self::Visual(VisualCommand::Select(VisualKind::Char))),
+            (KeyCode::Char('V'), _) => Ok(Self::Visual(VisualCommand::Select(VisualKind::Line))),
+            (KeyCode::Char(':'), _) => Ok(Self::Change(Mode::Command)),
+            (KeyCode::Char('q'), KeyModifiers::CONTROL) => Ok(Self::Quit),
+            (KeyCode::Char('s'), KeyModifiers::CONTROL) => Ok(Self::Save),
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => Ok(Self::Normal(NormalCommand::Redo)),
+            (KeyCode::Char('u'), KeyModifiers::NONE) => Ok(Self::Normal(NormalCommand::Undo)),
+            (KeyCode::F(5), _) => Ok(Self::ToggleUndoTree),
+            (KeyCode::Up | KeyCode::Char('k'), _) => {
+                Ok(Self::Normal(NormalCommand::Move(Direction::Up)))
+            }
+            (KeyCode::Down | KeyCode::Char('j'), _) => {
+                Ok(Self::Normal(NormalCommand::Move(Direction::Down)))
+            }
+            (KeyCode::Right | KeyCode::Char('l'), _) => {
+                Ok(Self::Normal(NormalCommand::Move(Direction::Right)))
+            }
+            (KeyCode::Left | KeyCode::Char('h'), _) => {
+                Ok(Self::Normal(NormalCommand::Move(Direction::Left)))
+            }
+            (KeyCode::PageDown, _) => Ok(Self::Normal(NormalCommand::Move(Direction::PageDown))),
+            (KeyCode::PageUp, _) => Ok(Self::Normal(NormalCommand::Move(Direction::PageUp))),
+            (KeyCode::Home, _) => Ok(Self::Normal(NormalCommand::Move(Direction::Home))),
+            (KeyCode::End, _) => Ok(Self::Normal(NormalCommand::Move(Direction::End))),
+            (KeyCode::Char('w'), _) => Ok(Self::motion(Motion::WordStart(WordKind::Word))),
+            (KeyCode::Char('W'), _) => Ok(Self::motion(Motion::WordStart(WordKind::BigWord))),
+            (KeyCode::Char('b'), _) => Ok(Self::motion(Motion::PreviousWordStart(WordKind::Word))),
+            (KeyCode::Char('B'), _) => {
+                Ok(Self::motion(Motion::PreviousWordStart(WordKind::BigWord)))
+            }
+            (KeyCode::Char('e'), _) => Ok(Self::motion(Motion::WordEnd(WordKind::Word))),
+            (KeyCode::Char('E'), _) => Ok(Self::motion(Motion::WordEnd(WordKind::BigWord))),
+            (KeyCode::Char('}'), _) => Ok(Self::motion(Motion::NextParagraph)),
+            (KeyCode::Char('{'), _) => Ok(Self::motion(Motion::PreviousParagraph)),
+            (KeyCode::Char('0'), _) => Ok(Self::motion(Motion::LineStart)),
+            (KeyCode::Char('^'), _) => Ok(Self::motion(Motion::FirstNonBlank)),
+            (KeyCode::Char('$'), _) => Ok(Self::motion(Motion::LineEnd)),
+            (KeyCode::Char('G'), _) => Ok(Self::motion(Motion::LastLine)),
+            (KeyCode::Char('x'), _) => Ok(Self::operate(Operator::Delete, Motion::Right)),
+            (KeyCode::Char('X'), _) => Ok(Self::operate(Operator::Delete, Motion::Left)),
+            (KeyCode::Char('D'), _) => Ok(Self::operate(Operator::Delete, Motion::LineEnd)),
+            (KeyCode::Char('C'), _) => Ok(Self::operate(Operator::Change, Motion::LineEnd)),
+            (KeyCode::Char('s'), _) => Ok(Self::operate(Operator::Change, Motion::Right)),
+            (KeyCode::Char('S'), _) => Ok(Self::Normal(NormalCommand::Operate(
+                Operator::Change,
+                Target::Lines,
+            ))),
+            (KeyCode::Char('p'), _) => Ok(Self::Normal(NormalCommand::Put { before: false })),
+            (KeyCode::Char('P'), _) => Ok(Self::Normal(NormalCommand::Put { before: true })),
+            (KeyCode::Char('.'), _) => Ok(Self::Normal(NormalCommand::Repeat)),
+            _ => Err(format!("Key Code not supported: {code:?}")),
+        }
+    }
+
+    fn from_prefixed_normal_command(prefix: &KeyEvent, key: &KeyEvent) -> Result<Self, String> {
+        let code = key.code;
+        let Some(prefix) = Self::prefix_of(prefix) else {
+            return Err(format!("Not the start of a command: {:?}", prefix.code));
+        };
+        match (prefix, code) {
+            ('g', KeyCode::Char('-')) => {
+                Ok(Self::Normal(NormalCommand::Earlier(TimeSpan::Steps(1))))
+            }
+            ('g', KeyCode::Char('+')) => Ok(Self::Normal(NormalCommand::Later(TimeSpan::Steps(1)))),
+            ('g', KeyCode::Char('g')) => Ok(Self::motion(Motion::FirstLine)),
+            ('g', KeyCode::Char('e')) => Ok(Self::motion(Motion::PreviousWordEnd(WordKind::Word))),
+            ('g', KeyCode::Char('E')) => {
+                Ok(Self::motion(Motion::PreviousWordEnd(WordKind::BigWord)))
+            }
+            ('g', KeyCode::Char('t')) => Ok(Self::Tab(TabCommand::Next)),
+            ('g', KeyCode::Char('T')) => Ok(Self::Tab(TabCommand::Previous)),
+            ('w', code) => Self::from_window_command(code),
+            ('m', KeyCode::Char(name @ ('a'..='z' | 'A'..='Z' | '\'' | '`' | '[' | ']'))) => {
+                Ok(Self::Normal(NormalCommand::SetMark(name)))
+            }
+            (
+                '\'' | '`',
+                KeyCode::Char(name @ ('a'..='z' | 'A'..='Z' | '\'' | '`' | '.' | '^' | '[' | ']')),
+            ) => Ok(Self::motion(Motion::Mark {
+                name,
+                exact: prefix == '`',
+            })),
+            ('q', KeyCode::Char(name @ ('a'..='z' | 'A'..='Z' | '0'..='9' | '"'))) => {
+                Ok(Self::Macro(MacroCommand::Record(name)))
+            }
+            ('@', KeyCode::Char(name)) if name == '@' || Registers::is_valid(name) => {
+                Ok(Self::Macro(MacroCommand::Play(name)))
+            }
+            _ => Err(format!("Key Code not supported after {prefix}: {code:?}")),
         }
     }
 
-    fn from_insert_command(event: Event) -> Result<Self, String> {
+    const fn motion(motion: Motion) -> Self {
+        Self::Normal(NormalCommand::Motion(motion))
+    }
+
+    const fn operate(operator: Operator, motion: Motion) -> Self {
+        Self::Normal(NormalCommand::Operate(operator, Target::Motion(motion)))
+    }
+
+    /// The key after Ctrl-W, with or without Ctrl held
+    fn from_window_command(code: KeyCode) -> Result<Self, String> {
+        let command = match code {
+            KeyCode::Char('s' | 'S') => WindowCommand::Split,
+            KeyCode::Char('v') => WindowCommand::VerticalSplit,
+            KeyCode::Char('h') | KeyCode::Left => WindowCommand::Focus(Direction::Left),
+            KeyCode::Char('j') | KeyCode::Down => WindowCommand::Focus(Direction::Down),
+            KeyCode::Char('k') | KeyCode::Up => WindowCommand::Focus(Direction::Up),
+            KeyCode::Char('l') | KeyCode::Right => WindowCommand::Focus(Direction::Right),
+            KeyCode::Char('w') => WindowCommand::Next,
+            KeyCode::Char('c' | 'q') => WindowCommand::Close,
+            KeyCode::Char('o') => WindowCommand::Only,
+            KeyCode::Char('=') => WindowCommand::Equalize,
+            _ => return Err(format!("Key Code not supported after Ctrl-W: {code:?}")),
+        };
+        Ok(Self::Window(command))
+    }
+
+    fn from_insert_command(event: &Event) -> Result<Self, String> {
         if let Event::Key(KeyEvent {
             code, modifiers, ..
-        }) = event
+        }) = *event
         {
             match (code, modifiers) {
                 (KeyCode::Char(a), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
@@ -136,9 +733,54 @@ impl EditorCommand {
         }
     }
 
-    // TODO: Temporary unused variables
-    #[allow(unused_variables)]
-    fn from_visual_command(event: Event) -> Result<Self, String> {
-        Ok(Self::Visual(VisualCommand::None))
+    fn from_command_line_command(event: &Event) -> Result<Self, String> {
+        if let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = *event
+        {
+            let command = match (code, modifiers) {
+                (KeyCode::Char(a), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    CommandLineCommand::Char(a)
+                }
+                (KeyCode::Backspace, _) => CommandLineCommand::Backspace,
+                (KeyCode::Delete, _) => CommandLineCommand::Delete,
+                (KeyCode::Left, _) => CommandLineCommand::Left,
+                (KeyCode::Right, _) => CommandLineCommand::Right,
+                (KeyCode::Home, _) => CommandLineCommand::Home,
+                (KeyCode::End, _) => CommandLineCommand::End,
+                (KeyCode::Enter, _) => CommandLineCommand::Submit,
+                _ => {
+                    return Err(format!(
+                        "Other event not supported in command mode: {event:?}"
+                    ));
+                }
+            };
+            Ok(Self::CommandLine(command))
+        } else {
+            Err(format!("Event not processed for command line: {event:?}"))
+        }
+    }
+
+    /// The single keys acting on the selection
+    fn from_visual_command(key: &KeyEvent) -> Option<VisualCommand> {
+        let command = match (key.code, key.modifiers) {
+            (KeyCode::Char('v'), KeyModifiers::CONTROL) => VisualCommand::Select(VisualKind::Block),
+            (KeyCode::Char('v'), _) => VisualCommand::Select(VisualKind::Char),
+            (KeyCode::Char('V'), _) => VisualCommand::Select(VisualKind::Line),
+            (KeyCode::Char('o'), _) => VisualCommand::SwapEnds,
+            (KeyCode::Char('d' | 'x') | KeyCode::Delete, _) => {
+                VisualCommand::Operate(Operator::Delete)
+            }
+            (KeyCode::Char('c' | 's'), _) => VisualCommand::Operate(Operator::Change),
+            (KeyCode::Char('y'), _) => VisualCommand::Operate(Operator::Yank),
+            (KeyCode::Char('>'), _) => VisualCommand::Operate(Operator::Indent),
+            (KeyCode::Char('<'), _) => VisualCommand::Operate(Operator::Outdent),
+            (KeyCode::Char('~'), _) => VisualCommand::Operate(Operator::ToggleCase),
+            (KeyCode::Char('u'), _) => VisualCommand::Operate(Operator::Lowercase),
+            (KeyCode::Char('U'), _) => VisualCommand::Operate(Operator::Uppercase),
+            (KeyCode::Char('J'), _) => VisualCommand::Join,
+            _ => return None,
+        };
+        Some(command)
     }
 }
@@ -0,0 +1,213 @@
+use std::time::Duration;
+
+use super::TimeSpan;
+
+/// A command entered on the `:` command line
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExCommand {
+    Write {
+        file: Option<String>,
+        force: bool,
+    },
+    WriteQuit {
+        file: Option<String>,
+        force: bool,
+    },
+    /// Write only when modified, then quit
+    Exit,
+    Quit {
+        force: bool,
+    },
+    Edit {
+        file: Option<String>,
+        force: bool,
+    },
+    SaveAs {
+        file: String,
+        force: bool,
+    },
+    /// Go to the 1-based line number
+    Goto(usize),
+    Earlier(TimeSpan),
+    Later(TimeSpan),
+    /// `:set` with its space separated arguments
+    Set(Vec<String>),
+    /// Switch between editing the text and the bytes of the buffer
+    HexEdit,
+    BufferNext,
+    BufferPrevious,
+    /// Go to the buffer with the given number or name
+    Buffer(String),
+    ListBuffers,
+    /// Split the window, optionally editing a file in the new one
+    Split(Option<String>),
+    VerticalSplit(Option<String>),
+    /// Close the window
+    Close {
+        force: bool,
+    },
+    /// Close all other windows
+    Only,
+    /// Open a tab page, with the file if given
+    TabNew(Option<String>),
+    /// Go to the next tab page, or to the one with the given number
+    TabNext(Option<usize>),
+    TabPrevious,
+    TabClose {
+        force: bool,
+    },
+    /// List the registers, only the ones named in the argument if given
+    Registers(Option<String>),
+}
+
+impl ExCommand {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if let Ok(line_number) = input.parse::<usize>() {
+            return Ok(Self::Goto(line_number));
+        }
+
+        let name_len = input
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(input.len());
+        let (name, rest) = input.split_at(name_len);
+        let (force, argument) = match rest.strip_prefix('!') {
+            Some(argument) => (true, argument.trim()),
+            None => (false, rest.trim()),
+        };
+        let argument = (!argument.is_empty()).then(|| argument.to_string());
+
+        let command = match name {
+            _ if Self::matches(name, "write", 1) => Self::Write {
+                file: argument,
+                force,
+            },
+            "wq" => Self::WriteQuit {
+                file: argument,
+                force,
+            },
+            _ if Self::matches(name, "xit", 1) || Self::matches(name, "exit", 3) => Self::Exit,
+            _ if Self::matches(name, "quit", 1) => {
+                Self::no_argument(name, argument.as_deref())?;
+                Self::Quit { force }
+            }
+            _ if Self::matches(name, "edit", 1) => Self::Edit {
+                file: argument,
+                force,
+            },
+            _ if Self::matches(name, "saveas", 3) => Self::SaveAs {
+                file: argument.ok_or_else(|| String::from("Argument required"))?,
+                force,
+            },
+            _ if Self::matches(name, "earlier", 2) => {
+                Self::Earlier(Self::parse_time_span(argument.as_deref())?)
+            }
+            _ if Self::matches(name, "set", 2) => Self::Set(
+                argument
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect(),
+            ),
+            _ if Self::matches(name, "hexedit", 3) => {
+                Self::no_argument(name, argument.as_deref())?;
+                Self::HexEdit
+            }
+            _ if Self::matches(name, "bnext", 2) => {
+                Self::no_argument(name, argument.as_deref())?;
+                Self::BufferNext
+            }
+            _ if Self::matches(name, "bprevious", 2) || Self::matches(name, "bNext", 2) => {
+                Self::no_argument(name, argument.as_deref())?;
+                Self::BufferPrevious
+            }
+            "ls" | "files" | "buffers" => Self::ListBuffers,
+            _ if Self::matches(name, "registers", 3) || Self::matches(name, "display", 2) => {
+                Self::Registers(argument)
+            }
+            "tabnew" | "tabedit" | "tabe" => Self::TabNew(argument),
+            _ if Self::matches(name, "tabnext", 4) => Self::TabNext(
+                argument
+                    .map(|argument| {
+                        argument
+                            .parse()
+                            .map_err(|_| format!("Invalid argument: {argument}"))
+                    })
+                    .transpose()?,
+            ),
+            _ if Self::matches(name, "tabprevious", 4) || Self::matches(name, "tabNext", 4) => {
+                Self::no_argument(name, argument.as_deref())?;
+                Self::TabPrevious
+            }
+            _ if Self::matches(name, "tabclose", 4) => {
+                Self::no_argument(name, argument.as_deref())?;
+                Self::TabClose { force }
+            }
+            _ if Self::matches(name, "split", 2) => Self::Split(argument),
+            _ if Self::matches(name, "vsplit", 2) => Self::VerticalSplit(argument),
+            _ if Self::matches(name, "close", 3) => {
+                Self::no_argument(name, argument.as_deref())?;
+                Self::Close { force }
+            }
+            _ if Self::matches(name, "only", 2) => {
+                Self::no_argument(name, argument.as_deref())?;
+                Self::Only
+            }
+            _ if Self::matches(name, "buffer", 1) => {
+                Self::Buffer(argument.ok_or_else(|| String::from("Argument required"))?)
+            }
+            _ if Self::matches(name, "later", 3) => {
+                Self::Later(Self::parse_time_span(argument.as_deref())?)
+            }
+            _ => return Err(format!("Not an editor command: {input}")),
+        };
+
+        Ok(command)
+    }
+
+    /// Vim style abbreviations: `name` is a prefix of `full` at least `min_len` long
+    fn matches(name: &str, full: &str, min_len: usize) -> bool {
+        name.len() >= min_len && full.starts_with(name)
+    }
+
+    fn no_argument(name: &str, argument: Option<&str>) -> Result<(), String> {
+        match argument {
+            Some(argument) => Err(format!("Trailing characters: {name} {argument}")),
+            None => Ok(()),
+        }
+    }
+
+    /// Parses `{N}` as a number of changes, or `{N}s`, `{N}m`, `{N}h`, `{N}d`
+    /// as a duration. Without an argument a single change is used.
+    fn parse_time_span(argument: Option<&str>) -> Result<TimeSpan, String> {
+        let Some(argument) = argument else {
+            return Ok(TimeSpan::Steps(1));
+        };
+        let invalid = || format!("Invalid argument: {argument}");
+
+        let digits_len = argument
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(argument.len());
+        let (count, unit) = argument.split_at(digits_len);
+        let count: u64 = count.parse().map_err(|_| invalid())?;
+
+        let seconds_per_unit: u64 = match unit {
+            "" => {
+                return usize::try_from(count)
+                    .map(TimeSpan::Steps)
+                    .map_err(|_| invalid());
+            }
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+
+        count
+            .checked_mul(seconds_per_unit)
+            .map(|seconds| TimeSpan::Duration(Duration::from_secs(seconds)))
+            .ok_or_else(invalid)
+    }
+}
@@ -0,0 +1,143 @@
+use super::{Counted, EditorCommand, Mode};
+
+#[derive(Clone, Default)]
+struct Change {
+    commands: Vec<Counted>,
+    /// The command making the change, after the ones selecting the text of
+    /// a change made in Visual mode
+    start: usize,
+}
+
+/// The commands of the last change to the text, which `.` makes again. A
+/// change starts with a command like `dw`, `p` or `i`, and after one going
+/// on in Insert mode, takes what is typed up to Esc.
+#[derive(Default)]
+pub struct LastChange {
+    last: Change,
+    recording: Option<Change>,
+}
+
+impl LastChange {
+    /// Starts recording a change, with the commands selecting its text in
+    /// Visual mode if any
+    pub fn start(&mut self, selection: Vec<Counted>, command: Counted) {
+        let start = selection.len();
+        let mut commands = selection;
+        commands.push(command);
+        self.recording = Some(Change { commands, start });
+    }
+
+    /// Adds a command to the change being recorded, like a key typed in
+    /// Insert mode
+    pub fn push(&mut self, command: Counted) {
+        if let Some(change) = &mut self.recording {
+            change.commands.push(command);
+        }
+    }
+
+    /// Ends the change being recorded, which becomes the last one
+    pub fn finish(&mut self) {
+        if let Some(change) = self.recording.take() {
+            self.last = change;
+        }
+    }
+
+    /// The commands to replay, with `count` instead of the count the change
+    /// was made with. After `i` it is how many times to insert the text.
+    pub fn replay(&self, count: Option<usize>) -> Vec<Counted> {
+        let Change {
+            mut commands,
+            start,
+        } = self.last.clone();
+        let Some(count) = count else {
+            return commands;
+        };
+        let Some(first) = commands.get_mut(start) else {
+            return commands;
+        };
+        if !matches!(first.command, EditorCommand::Change(Mode::Insert)) {
+            first.count = Some(count);
+            return commands;
+        }
+
+        let (first, typed) = commands.split_at(start.saturating_add(1));
+        let typed: Vec<Counted> = typed
+            .iter()
+            .copied()
+            .filter(|command| matches!(command.command, EditorCommand::Insert(_)))
+            .collect();
+        let mut replay = first.to_vec();
+        for _ in 0..count {
+            replay.extend_from_slice(&typed);
+        }
+        replay.push(Counted::from(EditorCommand::Esc));
+        replay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::command::{
+        InsertCommand, Motion, NormalCommand, Operator, Target, WordKind,
+    };
+
+    fn typed(text: &str) -> Vec<Counted> {
+        text.chars()
+            .map(|ch| Counted::from(EditorCommand::Insert(InsertCommand::Char(ch))))
+            .collect()
+    }
+
+    /// The commands as `Debug` shows them, as they can't be compared
+    fn shown(commands: &[Counted]) -> Vec<String> {
+        commands
+            .iter()
+            .map(|command| format!("{:?} {:?}", command.command, command.count))
+            .collect()
+    }
+
+    #[test]
+    fn a_count_replaces_the_one_of_the_change() {
+        let delete = EditorCommand::Normal(NormalCommand::Operate(
+            Operator::Delete,
+            Target::Motion(Motion::WordStart(WordKind::Word)),
+        ));
+        let mut last_change = LastChange::default();
+        last_change.start(Vec::new(), Counted::new(delete, Some(2), None));
+        last_change.finish();
+
+        assert_eq!(
+            shown(&last_change.replay(None)),
+            shown(&[Counted::new(delete, Some(2), None)])
+        );
+        assert_eq!(
+            shown(&last_change.replay(Some(5))),
+            shown(&[Counted::new(delete, Some(5), None)])
+        );
+    }
+
+    #[test]
+    fn a_count_inserts_the_typed_text_again() {
+        let insert = Counted::from(EditorCommand::Change(Mode::Insert));
+        let mut last_change = LastChange::default();
+        last_change.start(Vec::new(), insert);
+        for command in typed("ab") {
+            last_change.push(command);
+        }
+        last_change.push(Counted::from(EditorCommand::Esc));
+        last_change.finish();
+
+        let mut expected = vec![insert];
+        expected.extend(typed("abab"));
+        expected.push(Counted::from(EditorCommand::Esc));
+        assert_eq!(shown(&last_change.replay(Some(2))), shown(&expected));
+    }
+
+    #[test]
+    fn commands_outside_a_change_are_not_recorded() {
+        let mut last_change = LastChange::default();
+        last_change.push(Counted::from(EditorCommand::Esc));
+        last_change.finish();
+        assert!(last_change.replay(None).is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Keys without a character of their own are kept in a register as
+/// characters of the private use area
+const SPECIAL_KEYS: [KeyCode; 9] = [
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Delete,
+];
+const FIRST_SPECIAL_KEY: u32 = 0xE000;
+
+/// The keys typed while recording a macro with `q`, and the register last
+/// played with `@`, for `@@`
+#[derive(Default)]
+pub struct MacroRecorder {
+    recording: Option<(char, Vec<KeyEvent>)>,
+    last_played: Option<char>,
+}
+
+impl MacroRecorder {
+    /// The register being recorded into
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(name, _)| *name)
+    }
+
+    pub fn start(&mut self, name: char) {
+        self.recording = Some((name, Vec::new()));
+    }
+
+    pub fn push(&mut self, key: KeyEvent) {
+        if let Some((_, keys)) = &mut self.recording {
+            keys.push(key);
+        }
+    }
+
+    /// Ends the recording, returning the register and the keys as text
+    pub fn stop(&mut self) -> Option<(char, String)> {
+        let (name, keys) = self.recording.take()?;
+        Some((name, keys.iter().filter_map(|key| to_char(*key)).collect()))
+    }
+
+    pub const fn last_played(&self) -> Option<char> {
+        self.last_played
+    }
+
+    pub const fn set_last_played(&mut self, name: char) {
+        self.last_played = Some(name);
+    }
+}
+
+/// A key as it is kept in a register: Ctrl with a letter as a control
+/// character, Enter as `^M` and Esc as `^[`. `None` for the keys that
+/// can't be kept, like the function keys.
+fn to_char(key: KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(ch)
+            if key.modifiers.contains(KeyModifiers::CONTROL) && ch.is_ascii_alphabetic() =>
+        {
+            let byte = u8::try_from(ch.to_ascii_lowercase()).ok()?;
+            Some(char::from(byte & 0x1f))
+        }
+        KeyCode::Char(ch) => Some(ch),
+        KeyCode::Enter => Some('\r'),
+        KeyCode::Esc => Some('\x1b'),
+        KeyCode::Tab => Some('\t'),
+        KeyCode::Backspace => Some('\x7f'),
+        code => {
+            let index = SPECIAL_KEYS.iter().position(|&special| special == code)?;
+            let index = u32::try_from(index).ok()?;
+            char::from_u32(FIRST_SPECIAL_KEY.saturating_add(index))
+        }
+    }
+}
+
+/// The keys to play for the text of a register. A line break, as in text
+/// yanked from the buffer, is Enter.
+pub fn keys_from_text(text: &str) -> Vec<KeyEvent> {
+    text.chars().map(from_char).collect()
+}
+
+fn from_char(ch: char) -> KeyEvent {
+    let code = match ch {
+        '\r' | '\n' => KeyCode::Enter,
+        '\x1b' => KeyCode::Esc,
+        '\t' => KeyCode::Tab,
+        '\x7f' => KeyCode::Backspace,
+        '\x01'..='\x1a' => {
+            let letter = u8::try_from(ch).map_or('a', |byte| char::from(byte | 0x60));
+            return KeyEvent::new(KeyCode::Char(letter), KeyModifiers::CONTROL);
+        }
+        _ => u32::from(ch)
+            .checked_sub(FIRST_SPECIAL_KEY)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| SPECIAL_KEYS.get(index).copied())
+            .unwrap_or(KeyCode::Char(ch)),
+    };
+    KeyEvent::new(code, KeyModifiers::NONE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn keys_round_trip_through_the_register_text() {
+        let keys = vec![
+            key(KeyCode::Char('i')),
+            key(KeyCode::Char('é')),
+            key(KeyCode::Tab),
+            key(KeyCode::Enter),
+            key(KeyCode::Backspace),
+            key(KeyCode::Esc),
+            key(KeyCode::Up),
+            key(KeyCode::Delete),
+            KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL),
+        ];
+        let mut recorder = MacroRecorder::default();
+        recorder.start('a');
+        for &key in &keys {
+            recorder.push(key);
+        }
+        let (name, text) = recorder.stop().unwrap_or_default();
+        assert_eq!(name, 'a');
+        assert_eq!(text, "ié\t\r\x7f\x1b\u{E000}\u{E008}\x17");
+        assert_eq!(keys_from_text(&text), keys);
+    }
+
+    #[test]
+    fn only_keys_typed_while_recording_are_kept() {
+        let mut recorder = MacroRecorder::default();
+        recorder.push(key(KeyCode::Char('x')));
+        assert_eq!(recorder.stop(), None);
+
+        recorder.start('q');
+        assert_eq!(recorder.recording(), Some('q'));
+        recorder.push(key(KeyCode::F(1)));
+        recorder.push(key(KeyCode::Char('x')));
+        assert_eq!(recorder.stop(), Some(('q', String::from("x"))));
+        assert_eq!(recorder.recording(), None);
+    }
+
+    #[test]
+    fn yanked_line_breaks_play_as_enter() {
+        assert_eq!(
+            keys_from_text(":s\n"),
+            [
+                key(KeyCode::Char(':')),
+                key(KeyCode::Char('s')),
+                key(KeyCode::Enter)
+            ]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::{EditorCommand, Mode};
+
+/// A command with the count typed before it, e.g. the 5 of `5j`, and the
+/// register it uses, e.g. the `a` of `"ayy`
+#[derive(Debug, Clone, Copy)]
+pub struct Counted {
+    pub command: EditorCommand,
+    pub count: Option<usize>,
+    pub register: Option<char>,
+}
+
+impl Counted {
+    pub const fn new(command: EditorCommand, count: Option<usize>, register: Option<char>) -> Self {
+        Self {
+            command,
+            count,
+            register,
+        }
+    }
+}
+
+impl From<EditorCommand> for Counted {
+    fn from(command: EditorCommand) -> Self {
+        Self::new(command, None, None)
+    }
+}
+
+/// The keys typed in Normal or Visual mode that don't make a whole command
+/// yet, like the `2g` of `2gg`
+#[derive(Default)]
+pub struct PendingKeys {
+    keys: Vec<KeyEvent>,
+}
+
+impl PendingKeys {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+
+    /// Adds a key, returning the command once it is complete and `None`
+    /// while more keys are needed. Keys that make no command are dropped.
+    pub fn push(&mut self, key: KeyEvent, mode: Mode) -> Result<Option<Counted>, String> {
+        self.keys.push(key);
+        let result = if mode == Mode::Visual {
+            EditorCommand::from_visual_keys(&self.keys)
+        } else {
+            EditorCommand::from_normal_keys(&self.keys)
+        };
+        if !matches!(result, Ok(None)) {
+            self.clear();
+        }
+        result
+    }
+}
+
+/// The keys like vim's `showcmd`, e.g. `2d3`, `"a` or `^W`
+impl fmt::Display for PendingKeys {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for key in &self.keys {
+            match key.code {
+                KeyCode::Char(ch) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    write!(formatter, "^{}", ch.to_ascii_uppercase())?;
+                }
+                KeyCode::Char(ch) => write!(formatter, "{ch}")?,
+                code => write!(formatter, "<{code}>")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::command::{Motion, NormalCommand, Operator, Target, WordKind};
+
+    /// Types `keys` in Normal mode, returning the result of the last one
+    fn typed(pending: &mut PendingKeys, keys: &str) -> Result<Option<Counted>, String> {
+        let mut result = Ok(None);
+        for ch in keys.chars() {
+            result = pending.push(
+                KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE),
+                Mode::Normal,
+            );
+        }
+        result
+    }
+
+    fn command(keys: &str) -> Option<Counted> {
+        typed(&mut PendingKeys::default(), keys).ok().flatten()
+    }
+
+    #[test]
+    fn counts_before_the_operator_and_the_motion_multiply() {
+        let counted = command("2d3w");
+        assert!(matches!(
+            counted.map(|counted| counted.command),
+            Some(EditorCommand::Normal(NormalCommand::Operate(
+                Operator::Delete,
+                Target::Motion(Motion::WordStart(WordKind::Word))
+            )))
+        ));
+        assert_eq!(counted.and_then(|counted| counted.count), Some(6));
+        assert_eq!(
+            command("999999j").and_then(|counted| counted.count),
+            Some(99_999)
+        );
+    }
+
+    #[test]
+    fn a_count_goes_around_the_register() {
+        let counted = command("3\"a2yy");
+        assert_eq!(counted.and_then(|counted| counted.register), Some('a'));
+        assert_eq!(counted.and_then(|counted| counted.count), Some(6));
+    }
+
+    #[test]
+    fn zero_is_a_motion_unless_it_continues_a_count() {
+        let counted = command("0");
+        assert!(matches!(
+            counted.map(|counted| counted.command),
+            Some(EditorCommand::Normal(NormalCommand::Motion(
+                Motion::LineStart
+            )))
+        ));
+        assert_eq!(counted.and_then(|counted| counted.count), None);
+        assert_eq!(command("10j").and_then(|counted| counted.count), Some(10));
+    }
+
+    #[test]
+    fn unfinished_keys_wait_and_wrong_ones_are_dropped() {
+        let mut pending = PendingKeys::default();
+        assert!(matches!(typed(&mut pending, "2d3"), Ok(None)));
+        assert_eq!(pending.to_string(), "2d3");
+        pending.clear();
+
+        assert!(typed(&mut pending, "\"!").is_err());
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::Error;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{
+    command::CommandLineCommand,
+    terminal::{Size, Terminal},
+    uicomponent::UIComponent,
+};
+
+/// What the input of the command bar is asked for
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PromptType {
+    #[default]
+    Command,
+    SaveAs,
+}
+
+impl PromptType {
+    pub fn prompt(self) -> &'static str {
+        match self {
+            Self::Command => ":",
+            Self::SaveAs => "Save as: ",
+        }
+    }
+}
+
+/// Single line prompt rendered in place of the message bar, e.g. the `:`
+/// command line
+#[derive(Default)]
+pub struct CommandBar {
+    prompt: String,
+    value: String,
+    /// Grapheme index of the caret within `value`
+    cursor: usize,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl CommandBar {
+    pub fn start(&mut self, prompt: &str) {
+        self.prompt = prompt.to_string();
+        self.value.clear();
+        self.cursor = 0;
+        self.set_needs_redraw(true);
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn handle_command(&mut self, command: CommandLineCommand) {
+        match command {
+            CommandLineCommand::Char(character) => {
+                let at = self.byte_index(self.cursor);
+                self.value.insert(at, character);
+                self.cursor = self.cursor.saturating_add(1);
+            }
+            CommandLineCommand::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor = self.cursor.saturating_sub(1);
+                    self.remove_grapheme(self.cursor);
+                }
+            }
+            CommandLineCommand::Delete => self.remove_grapheme(self.cursor),
+            CommandLineCommand::Left => self.cursor = self.cursor.saturating_sub(1),
+            CommandLineCommand::Right => {
+                self.cursor = self.cursor.saturating_add(1).min(self.grapheme_count());
+            }
+            CommandLineCommand::Home => self.cursor = 0,
+            CommandLineCommand::End => self.cursor = self.grapheme_count(),
+            CommandLineCommand::Submit => {}
+        }
+        // A typed char may have merged with the previous grapheme
+        self.cursor = self.cursor.min(self.grapheme_count());
+        self.set_needs_redraw(true);
+    }
+
+    /// Column of the caret, relative to the start of the row
+    pub fn caret_col(&self) -> usize {
+        let before_caret = self
+            .value
+            .get(..self.byte_index(self.cursor))
+            .unwrap_or_default();
+        let width = self.prompt.width().saturating_add(before_caret.width());
+        width.min(self.size.width.saturating_sub(1))
+    }
+
+    fn grapheme_count(&self) -> usize {
+        self.value.graphemes(true).count()
+    }
+
+    fn byte_index(&self, grapheme_index: usize) -> usize {
+        self.value
+            .grapheme_indices(true)
+            .nth(grapheme_index)
+            .map_or(self.value.len(), |(index, _)| index)
+    }
+
+    fn remove_grapheme(&mut self, grapheme_index: usize) {
+        let start = self.byte_index(grapheme_index);
+        let end = self.byte_index(grapheme_index.saturating_add(1));
+        self.value.replace_range(start..end, "");
+    }
+}
+
+impl UIComponent for CommandBar {
+    fn set_needs_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+        let line = format!("{}{}", self.prompt, self.value);
+        let mut width = 0;
+        let visible: String = line
+            .graphemes(true)
+            .take_while(|grapheme| {
+                width = grapheme.width().saturating_add(width);
+                width <= self.size.width
+            })
+            .collect();
+
+        Terminal::print_row(origin_y, &visible)
+    }
+}
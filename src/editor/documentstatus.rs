@@ -1,4 +1,4 @@
-use super::Mode;
+use super::{Mode, fileinfo::FileFormat};
 
 #[derive(Default, Eq, PartialEq, Debug)]
 pub struct DocumentStatus {
@@ -7,6 +7,16 @@ pub struct DocumentStatus {
     pub is_modified: bool,
     pub file_name: String,
     pub mode: Mode,
+    pub file_format: FileFormat,
+    /// The buffer is shown as bytes
+    pub is_hex: bool,
+    /// 1-based number of the buffer in the buffer list
+    pub buffer_number: usize,
+    pub buffer_count: usize,
+    /// Keys of an unfinished command, e.g. `2g`
+    pub pending_keys: String,
+    /// The register a macro is being recorded into
+    pub recording: Option<char>,
 }
 
 impl DocumentStatus {
@@ -18,6 +28,24 @@ impl DocumentStatus {
         }
     }
 
+    pub fn buffer_indicator_to_string(&self) -> String {
+        format!("[{}/{}]", self.buffer_number, self.buffer_count)
+    }
+
+    pub fn recording_to_string(&self) -> String {
+        self.recording
+            .map(|name| format!("recording @{name} "))
+            .unwrap_or_default()
+    }
+
+    pub fn pending_keys_to_string(&self) -> String {
+        if self.pending_keys.is_empty() {
+            String::new()
+        } else {
+            format!("{}   ", self.pending_keys)
+        }
+    }
+
     pub fn line_count_to_string(&self) -> String {
         format!("{} lines", self.total_lines)
     }
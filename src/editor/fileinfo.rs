@@ -3,15 +3,138 @@ use std::{
     path::PathBuf,
 };
 
+use super::options::OptionAction;
+
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lf => write!(formatter, "unix"),
+            Self::Crlf => write!(formatter, "dos"),
+        }
+    }
+}
+
+/// The character encoding of a file
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Maps every byte to the char of the same value, so any file can be
+    /// loaded and written back unchanged
+    Latin1,
+}
+
+impl Encoding {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "utf-16le" | "utf16le" => Some(Self::Utf16Le),
+            "utf-16be" | "utf16be" | "utf-16" | "utf16" => Some(Self::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Self::Latin1),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+            Self::Latin1 => "latin1",
+        };
+        write!(formatter, "{name}")
+    }
+}
+
+/// How the text is laid out on disk, remembered from loading so that
+/// saving writes it back the same way
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FileFormat {
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    /// The last line is not terminated by a line ending
+    pub missing_final_newline: bool,
+    /// The file starts with a byte order mark
+    pub bom: bool,
+}
+
+impl FileFormat {
+    /// Applies a buffer local `:set` argument: `fileencoding`/`fenc`,
+    /// `fileformat`/`ff`, `eol` or `bomb`. Returns `None` when the option is
+    /// not one of them.
+    pub fn set(&mut self, argument: &str) -> Option<Result<Option<String>, String>> {
+        let (name, action) = OptionAction::parse(argument);
+        let result = match name {
+            "fileencoding" | "fenc" => match action {
+                OptionAction::Assign(value) => Encoding::from_name(value)
+                    .map(|encoding| {
+                        self.encoding = encoding;
+                        None
+                    })
+                    .ok_or_else(|| format!("Invalid argument: {argument}")),
+                _ => Ok(Some(format!("fileencoding={}", self.encoding))),
+            },
+            "fileformat" | "ff" => match action {
+                OptionAction::Assign("unix") => {
+                    self.line_ending = LineEnding::Lf;
+                    Ok(None)
+                }
+                OptionAction::Assign("dos") => {
+                    self.line_ending = LineEnding::Crlf;
+                    Ok(None)
+                }
+                OptionAction::Assign(_) => Err(format!("Invalid argument: {argument}")),
+                _ => Ok(Some(format!("fileformat={}", self.line_ending))),
+            },
+            "endofline" | "eol" => {
+                let mut final_newline = !self.missing_final_newline;
+                let result = action.apply_to_flag(&mut final_newline, "endofline");
+                self.missing_final_newline = !final_newline;
+                result
+            }
+            "bomb" => action.apply_to_flag(&mut self.bom, "bomb"),
+            _ => return None,
+        };
+
+        Some(result)
+    }
+}
+
+impl Display for FileFormat {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{} {}", self.encoding, self.line_ending)?;
+        if self.missing_final_newline {
+            write!(formatter, " noeol")?;
+        }
+        if self.bom {
+            write!(formatter, " bom")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct FileInfo {
     pub path: Option<PathBuf>,
+    pub format: FileFormat,
 }
 
 impl FileInfo {
     pub fn from(file_name: &str) -> Self {
         Self {
             path: Some(PathBuf::from(file_name)),
+            format: FileFormat::default(),
         }
     }
 }
@@ -0,0 +1,623 @@
+use std::rc::Rc;
+
+use super::{
+    command::Direction,
+    terminal::{Position, Size, Terminal},
+    uicomponent::UIComponent,
+    view::View,
+};
+
+/// How the windows of a split are arranged
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SplitDirection {
+    /// Windows above each other, made by `:split`
+    Horizontal,
+    /// Windows next to each other, made by `:vsplit`
+    Vertical,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Rect {
+    origin: Position,
+    size: Size,
+}
+
+impl Rect {
+    fn contains(&self, position: Position) -> bool {
+        (self.origin.row..self.origin.row.saturating_add(self.size.height)).contains(&position.row)
+            && (self.origin.col..self.origin.col.saturating_add(self.size.width))
+                .contains(&position.col)
+    }
+
+    const fn bottom(&self) -> usize {
+        self.origin.row.saturating_add(self.size.height)
+    }
+
+    const fn extent(&self, direction: SplitDirection) -> usize {
+        match direction {
+            SplitDirection::Horizontal => self.size.height,
+            SplitDirection::Vertical => self.size.width,
+        }
+    }
+}
+
+enum Node {
+    /// Index into `Layout::windows`
+    Window(usize),
+    Split {
+        direction: SplitDirection,
+        children: Vec<Child>,
+    },
+}
+
+struct Child {
+    node: Node,
+    /// Rows or columns of the child along the split. Only the proportions
+    /// matter, the space is shared out again whenever the layout changes.
+    size: usize,
+}
+
+impl Node {
+    fn first_window(&self) -> usize {
+        match self {
+            Self::Window(index) => *index,
+            Self::Split { children, .. } => children
+                .first()
+                .map_or(0, |child| child.node.first_window()),
+        }
+    }
+
+    /// Puts the window `new` before `window`, sharing its space. `extent`
+    /// is the space `window` has along `direction`.
+    #[allow(clippy::integer_division)]
+    fn split(
+        &mut self,
+        window: usize,
+        new: usize,
+        direction: SplitDirection,
+        extent: usize,
+    ) -> bool {
+        match self {
+            Self::Window(index) if *index == window => {
+                let half = extent / 2;
+                *self = Self::Split {
+                    direction,
+                    children: vec![
+                        Child {
+                            node: Self::Window(new),
+                            size: extent.saturating_sub(half),
+                        },
+                        Child {
+                            node: Self::Window(window),
+                            size: half,
+                        },
+                    ],
+                };
+                true
+            }
+            Self::Window(_) => false,
+            Self::Split {
+                direction: split_direction,
+                children,
+            } => {
+                let position = children
+                    .iter()
+                    .position(|child| matches!(child.node, Self::Window(index) if index == window));
+                if let Some(position) = position
+                    && *split_direction == direction
+                    && let Some(child) = children.get_mut(position)
+                {
+                    // Another window in the same direction, no need to nest
+                    let half = child.size / 2;
+                    let size = child.size.saturating_sub(half);
+                    child.size = half;
+                    children.insert(
+                        position,
+                        Child {
+                            node: Self::Window(new),
+                            size,
+                        },
+                    );
+                    return true;
+                }
+                children
+                    .iter_mut()
+                    .any(|child| child.node.split(window, new, direction, extent))
+            }
+        }
+    }
+
+    /// Takes `window` out of the tree, giving its space to the window before
+    /// it, or after it if it was the first. Returns the window which got
+    /// the space.
+    fn remove(&mut self, window: usize) -> Option<usize> {
+        let Self::Split { children, .. } = self else {
+            return None;
+        };
+        let Some(position) = children
+            .iter()
+            .position(|child| matches!(child.node, Self::Window(index) if index == window))
+        else {
+            return children
+                .iter_mut()
+                .find_map(|child| child.node.remove(window));
+        };
+
+        let removed = children.remove(position);
+        let neighbour = children.get_mut(position.saturating_sub(1))?;
+        neighbour.size = neighbour.size.saturating_add(removed.size);
+        let focus = neighbour.node.first_window();
+        if children.len() == 1
+            && let Some(child) = children.pop()
+        {
+            *self = child.node;
+        }
+        Some(focus)
+    }
+
+    /// Shifts the window indices after `removed` down by one
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Self::Window(index) if *index > removed => *index = index.saturating_sub(1),
+            Self::Window(_) => {}
+            Self::Split { children, .. } => {
+                for child in children {
+                    child.node.renumber(removed);
+                }
+            }
+        }
+    }
+
+    fn equalize(&mut self) {
+        if let Self::Split { children, .. } = self {
+            for child in children {
+                child.size = 1;
+                child.node.equalize();
+            }
+        }
+    }
+
+    /// Assigns screen space to every window below this node. A window gets
+    /// its own status line, unless it is at the bottom of the `area`.
+    #[allow(clippy::integer_division, clippy::arithmetic_side_effects)]
+    fn place(&mut self, rect: Rect, area: Rect, placement: &mut Placement) {
+        match self {
+            Self::Window(index) => {
+                if let Some(window_rect) = placement.rects.get_mut(*index) {
+                    *window_rect = rect;
+                }
+                if rect.bottom() < area.bottom() && rect.size.height > 0 {
+                    placement.status_lines.push(*index);
+                }
+            }
+            Self::Split {
+                direction,
+                children,
+            } => {
+                let direction = *direction;
+                // Windows next to each other are divided by a column
+                let separators = match direction {
+                    SplitDirection::Horizontal => 0,
+                    SplitDirection::Vertical => children.len().saturating_sub(1),
+                };
+                let available = rect.extent(direction).saturating_sub(separators);
+                let weight: usize = children.iter().map(|child| child.size).sum();
+
+                let mut offset = 0;
+                let mut remaining = available;
+                let count = children.len();
+                for (position, child) in children.iter_mut().enumerate() {
+                    let size = if position + 1 == count {
+                        remaining
+                    } else {
+                        (available * child.size)
+                            .checked_div(weight)
+                            .unwrap_or(available / count)
+                            .min(remaining)
+                    };
+                    remaining -= size;
+                    child.size = size;
+
+                    let child_rect = match direction {
+                        SplitDirection::Horizontal => Rect {
+                            origin: Position {
+                                row: rect.origin.row + offset,
+                                col: rect.origin.col,
+                            },
+                            size: Size {
+                                height: size,
+                                width: rect.size.width,
+                            },
+                        },
+                        SplitDirection::Vertical => Rect {
+                            origin: Position {
+                                row: rect.origin.row,
+                                col: rect.origin.col + offset,
+                            },
+                            size: Size {
+                                height: rect.size.height,
+                                width: size,
+                            },
+                        },
+                    };
+                    child.node.place(child_rect, area, placement);
+
+                    offset += size;
+                    if direction == SplitDirection::Vertical && position + 1 < count {
+                        placement.separators.push(Rect {
+                            origin: Position {
+                                row: rect.origin.row,
+                                col: rect.origin.col + offset,
+                            },
+                            size: Size {
+                                height: rect.size.height,
+                                width: 1,
+                            },
+                        });
+                        offset += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Placement {
+    /// Screen space of each window, including its status line
+    rects: Vec<Rect>,
+    /// Windows which show a status line in their last row
+    status_lines: Vec<usize>,
+    /// Columns between windows next to each other
+    separators: Vec<Rect>,
+}
+
+/// The windows shown on screen and how they are split. Every window is a
+/// `View` of its own, several of them may show the same buffer.
+pub struct Layout {
+    windows: Vec<View>,
+    root: Node,
+    active: usize,
+    area: Rect,
+    placement: Placement,
+    /// The status lines as last drawn
+    status_labels: Vec<String>,
+    needs_redraw: bool,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            windows: vec![View::default()],
+            root: Node::Window(0),
+            active: 0,
+            area: Rect::default(),
+            placement: Placement::default(),
+            status_labels: Vec::new(),
+            needs_redraw: true,
+        }
+    }
+}
+
+impl Layout {
+    pub fn active(&self) -> &View {
+        // There is always at least one window
+        self.windows
+            .get(self.active)
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    pub fn active_mut(&mut self) -> &mut View {
+        self.windows
+            .get_mut(self.active)
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    /// Draws everything again, e.g. when coming back to a tab page
+    pub fn set_needs_redraw(&mut self) {
+        for view in &mut self.windows {
+            view.set_needs_redraw(true);
+        }
+        self.needs_redraw = true;
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = &View> {
+        self.windows.iter()
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Sets the screen space of all windows, starting at `origin_row`
+    pub fn resize(&mut self, origin_row: usize, size: Size) {
+        self.area = Rect {
+            origin: Position {
+                row: origin_row,
+                col: 0,
+            },
+            size,
+        };
+        self.arrange();
+    }
+
+    /// Shares out the area among the windows again
+    fn arrange(&mut self) {
+        let mut placement = Placement {
+            rects: vec![Rect::default(); self.windows.len()],
+            ..Placement::default()
+        };
+        self.root.place(self.area, self.area, &mut placement);
+
+        for (index, (view, rect)) in self.windows.iter_mut().zip(&placement.rects).enumerate() {
+            let has_status_line = placement.status_lines.contains(&index);
+            view.set_origin_col(rect.origin.col);
+            view.resize(Size {
+                height: rect
+                    .size
+                    .height
+                    .saturating_sub(usize::from(has_status_line)),
+                width: rect.size.width,
+            });
+        }
+        self.placement = placement;
+        self.needs_redraw = true;
+    }
+
+    /// Splits the active window in two, both showing the same buffer at the
+    /// same place. The new window becomes the active one.
+    pub fn split(&mut self, direction: SplitDirection) -> Result<(), String> {
+        let rect = self
+            .placement
+            .rects
+            .get(self.active)
+            .copied()
+            .unwrap_or_default();
+        if rect.extent(direction) < 3 {
+            return Err(String::from("Not enough room"));
+        }
+
+        let mut view = View::default();
+        view.resize(self.active().size());
+        view.set_buffer(Rc::clone(self.active().buffer()), self.active().state());
+        self.windows.push(view);
+        let new = self.windows.len().saturating_sub(1);
+
+        self.root
+            .split(self.active, new, direction, rect.extent(direction));
+        self.active = new;
+        self.arrange();
+        Ok(())
+    }
+
+    /// Closes the active window. The last window can't be closed.
+    pub fn close(&mut self) -> Result<(), String> {
+        if self.windows.len() <= 1 {
+            return Err(String::from("Cannot close last window"));
+        }
+        let removed = self.active;
+        let focus = self.root.remove(removed).unwrap_or_default();
+        self.windows.remove(removed);
+        self.root.renumber(removed);
+        self.active = if focus > removed {
+            focus.saturating_sub(1)
+        } else {
+            focus
+        };
+        self.arrange();
+        Ok(())
+    }
+
+    /// Closes all windows but the active one
+    pub fn only(&mut self) {
+        let view = self.windows.swap_remove(self.active);
+        self.windows = vec![view];
+        self.root = Node::Window(0);
+        self.active = 0;
+        self.arrange();
+    }
+
+    /// Makes all windows (almost) the same size
+    pub fn equalize(&mut self) {
+        self.root.equalize();
+        self.arrange();
+    }
+
+    /// Moves to the window next to the active one in `direction`, at the
+    /// height or column of the caret
+    pub fn focus(&mut self, direction: Direction) {
+        let rect = self
+            .placement
+            .rects
+            .get(self.active)
+            .copied()
+            .unwrap_or_default();
+        let caret = self.caret_position();
+        let probe = match direction {
+            Direction::Left => rect.origin.col.checked_sub(2).map(|col| Position {
+                col,
+                row: caret.row,
+            }),
+            Direction::Right => Some(Position {
+                col: rect
+                    .origin
+                    .col
+                    .saturating_add(rect.size.width)
+                    .saturating_add(1),
+                row: caret.row,
+            }),
+            Direction::Up => rect.origin.row.checked_sub(1).map(|row| Position {
+                row,
+                col: caret.col,
+            }),
+            Direction::Down => Some(Position {
+                row: rect.bottom(),
+                col: caret.col,
+            }),
+            _ => None,
+        };
+        if let Some(index) = probe.and_then(|probe| {
+            self.placement
+                .rects
+                .iter()
+                .position(|rect| rect.contains(probe))
+        }) {
+            self.active = index;
+        }
+    }
+
+    /// Moves to the next window, wrapping around after the last one
+    #[allow(clippy::arithmetic_side_effects)]
+    pub fn focus_next(&mut self) {
+        self.active = (self.active + 1) % self.windows.len();
+    }
+
+    /// Where the caret of the active window is on screen
+    pub fn caret_position(&self) -> Position {
+        let origin = self
+            .placement
+            .rects
+            .get(self.active)
+            .map_or_else(Position::default, |rect| rect.origin);
+        let caret = self.active().caret_position();
+        Position {
+            row: origin.row.saturating_add(caret.row),
+            col: origin.col.saturating_add(caret.col),
+        }
+    }
+
+    pub fn render(&mut self) {
+        for (view, rect) in self.windows.iter_mut().zip(&self.placement.rects) {
+            view.sync_with_buffer();
+            if rect.size.height > 0 {
+                view.render(rect.origin.row);
+            }
+        }
+
+        let labels: Vec<String> = self
+            .placement
+            .status_lines
+            .iter()
+            .filter_map(|&index| self.windows.get(index))
+            .map(|view| {
+                let status = view.get_status();
+                format!(
+                    "{} {}",
+                    status.file_name,
+                    status.modified_indicator_to_string()
+                )
+            })
+            .collect();
+        if !self.needs_redraw && labels == self.status_labels {
+            return;
+        }
+
+        for (&index, label) in self.placement.status_lines.iter().zip(&labels) {
+            if let Some(rect) = self.placement.rects.get(index) {
+                let position = Position {
+                    row: rect.bottom().saturating_sub(1),
+                    col: rect.origin.col,
+                };
+                let _ = Terminal::print_inverted_at(position, label, rect.size.width);
+            }
+        }
+        for separator in &self.placement.separators {
+            for row in separator.origin.row..separator.bottom() {
+                let position = Position {
+                    row,
+                    col: separator.origin.col,
+                };
+                let _ = Terminal::print_at(position, "|");
+            }
+        }
+        self.status_labels = labels;
+        self.needs_redraw = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> Layout {
+        let mut layout = Layout::default();
+        layout.resize(
+            0,
+            Size {
+                height: 24,
+                width: 80,
+            },
+        );
+        layout
+    }
+
+    fn rect(layout: &Layout, window: usize) -> (usize, usize, usize, usize) {
+        let rect = layout
+            .placement
+            .rects
+            .get(window)
+            .copied()
+            .unwrap_or_default();
+        (
+            rect.origin.row,
+            rect.origin.col,
+            rect.size.height,
+            rect.size.width,
+        )
+    }
+
+    #[test]
+    fn splits_share_the_space_of_the_window() {
+        let mut layout = layout();
+        assert_eq!(layout.split(SplitDirection::Horizontal), Ok(()));
+        // The new window is above and active
+        assert_eq!(layout.active, 1);
+        assert_eq!(rect(&layout, 1), (0, 0, 12, 80));
+        assert_eq!(rect(&layout, 0), (12, 0, 12, 80));
+        assert_eq!(layout.placement.status_lines, [1]);
+
+        assert_eq!(layout.split(SplitDirection::Vertical), Ok(()));
+        assert_eq!(rect(&layout, 2), (0, 0, 12, 39));
+        assert_eq!(rect(&layout, 1), (0, 40, 12, 40));
+    }
+
+    #[test]
+    fn focus_goes_to_the_window_next_to_the_active_one() {
+        let mut layout = layout();
+        let _ = layout.split(SplitDirection::Horizontal);
+        let _ = layout.split(SplitDirection::Vertical);
+
+        layout.focus(Direction::Right);
+        assert_eq!(layout.active, 1);
+        layout.focus(Direction::Right);
+        assert_eq!(layout.active, 1);
+        layout.focus(Direction::Down);
+        assert_eq!(layout.active, 0);
+        layout.focus(Direction::Up);
+        assert_eq!(layout.active, 2);
+        layout.focus_next();
+        layout.focus_next();
+        assert_eq!(layout.active, 1);
+    }
+
+    #[test]
+    fn closing_gives_the_space_to_a_neighbour() {
+        let mut layout = layout();
+        let _ = layout.split(SplitDirection::Horizontal);
+        let _ = layout.split(SplitDirection::Vertical);
+
+        assert_eq!(layout.close(), Ok(()));
+        assert_eq!(layout.window_count(), 2);
+        assert_eq!(layout.active, 1);
+        assert_eq!(rect(&layout, 1), (0, 0, 12, 80));
+
+        layout.focus(Direction::Down);
+        assert_eq!(layout.close(), Ok(()));
+        assert_eq!(layout.active, 0);
+        assert_eq!(rect(&layout, 0), (0, 0, 24, 80));
+        assert_eq!(
+            layout.close(),
+            Err(String::from("Cannot close last window"))
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use super::clipboard::ClipboardProvider;
+
+/// What a single `:set` argument asks for
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OptionAction<'a> {
+    /// `name`
+    Enable,
+    /// `noname`
+    Disable,
+    /// `invname`
+    Toggle,
+    /// `name?`
+    Query,
+    /// `name=value`
+    Assign(&'a str),
+}
+
+impl<'a> OptionAction<'a> {
+    /// Splits a `:set` argument into the option name and the action
+    pub fn parse(argument: &'a str) -> (&'a str, Self) {
+        if let Some((name, value)) = argument.split_once('=') {
+            (name, Self::Assign(value))
+        } else if let Some(name) = argument.strip_suffix('?') {
+            (name, Self::Query)
+        } else if let Some(name) = argument.strip_prefix("no") {
+            (name, Self::Disable)
+        } else if let Some(name) = argument.strip_prefix("inv") {
+            (name, Self::Toggle)
+        } else {
+            (argument, Self::Enable)
+        }
+    }
+
+    /// Applies the action to a number option, which must be at least 1,
+    /// returning the message for a query
+    pub fn apply_to_number(self, number: &mut usize, name: &str) -> Result<Option<String>, String> {
+        match self {
+            Self::Assign(value) => match value.parse() {
+                Ok(value) if value > 0 => *number = value,
+                _ => return Err(format!("Invalid argument: {name}={value}")),
+            },
+            Self::Enable | Self::Query => return Ok(Some(format!("{name}={number}"))),
+            Self::Disable | Self::Toggle => return Err(format!("Invalid argument: {name}")),
+        }
+        Ok(None)
+    }
+
+    /// Applies the action to a boolean option, returning the message for a query
+    pub fn apply_to_flag(self, flag: &mut bool, name: &str) -> Result<Option<String>, String> {
+        match self {
+            Self::Enable => *flag = true,
+            Self::Disable => *flag = false,
+            Self::Toggle => *flag = !*flag,
+            Self::Query => {
+                let prefix = if *flag { "" } else { "no" };
+                return Ok(Some(format!("{prefix}{name}")));
+            }
+            Self::Assign(_) => return Err(format!("Invalid argument: {name}=")),
+        }
+        Ok(None)
+    }
+}
+
+/// Editor wide settings, changed with `:set`
+pub struct Options {
+    /// Keep the previous version of a file as `file~` when saving
+    pub backup: bool,
+    /// What `"+` and `"*` copy to and paste from
+    pub clipboard: ClipboardProvider,
+    /// The columns `>` and `<` add or remove
+    pub shiftwidth: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            backup: false,
+            clipboard: ClipboardProvider::default(),
+            shiftwidth: 4,
+        }
+    }
+}
+
+impl Options {
+    /// Applies a single `:set` argument like `backup`, `nobackup`,
+    /// `invbackup`, `backup?`, `clipboard=xclip` or `sw=2`. Returns a message to show, if any.
+    pub fn set(&mut self, argument: &str) -> Result<Option<String>, String> {
+        let (name, action) = OptionAction::parse(argument);
+        match name {
+            "backup" | "bk" => action.apply_to_flag(&mut self.backup, "backup"),
+            "clipboard" | "cb" => match action {
+                OptionAction::Assign(value) => {
+                    self.clipboard = ClipboardProvider::parse(value)?;
+                    Ok(None)
+                }
+                OptionAction::Enable | OptionAction::Query => {
+                    Ok(Some(format!("clipboard={}", self.clipboard)))
+                }
+                _ => Err(format!("Invalid argument: {argument}")),
+            },
+            "shiftwidth" | "sw" => action.apply_to_number(&mut self.shiftwidth, "shiftwidth"),
+            _ => Err(format!("Unknown option: {argument}")),
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+
+/// How the text of a register is put back
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum RegisterKind {
+    /// Into the line, at the cursor
+    #[default]
+    Charwise,
+    /// As whole lines, above or below the cursor
+    Linewise,
+    /// As a block, each line into a line of its own from the cursor column
+    Blockwise,
+}
+
+/// Text yanked or deleted, to be put back with `p`
+#[derive(Clone, Debug, Default)]
+pub struct Register {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+impl Register {
+    const fn charwise(text: String) -> Self {
+        Self {
+            text,
+            kind: RegisterKind::Charwise,
+        }
+    }
+
+    /// Adds `other` at the end, as for `"Ay`. Lines stay lines, so appending
+    /// to or from a linewise register makes it linewise.
+    fn append(&mut self, other: Self) {
+        if self.text.is_empty() {
+            *self = other;
+            return;
+        }
+        if self.kind == RegisterKind::Linewise || other.kind == RegisterKind::Linewise {
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.kind = RegisterKind::Linewise;
+        }
+        self.text.push_str(&other.text);
+        if self.kind == RegisterKind::Linewise && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+}
+
+/// Characters shown by `:registers` for each register
+const MAX_SHOWN: usize = 40;
+
+/// `c`, `l` or `b` like in the type column of `:registers`
+impl fmt::Display for RegisterKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            Self::Charwise => 'c',
+            Self::Linewise => 'l',
+            Self::Blockwise => 'b',
+        };
+        write!(formatter, "{kind}")
+    }
+}
+
+/// The start of the text, with line breaks and other control characters
+/// shown like `^J`
+impl fmt::Display for Register {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for ch in self.text.chars().take(MAX_SHOWN) {
+            match u8::try_from(ch) {
+                Ok(byte) if ch.is_ascii_control() => {
+                    write!(formatter, "^{}", char::from(byte ^ 0x40))?;
+                }
+                _ => write!(formatter, "{ch}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All the registers text is yanked and deleted into:
+/// - `""` the unnamed register, with the last text yanked or deleted
+/// - `"0` the last yank, `"1` to `"9` the last deletes of a line or more
+/// - `"-` the last delete within a line
+/// - `"a` to `"z`, which `"A` to `"Z` append to
+/// - `"+` the clipboard and `"*` the primary selection, which the editor
+///   also copies out of and pastes from
+/// - `"_` which drops what is written to it
+/// - `".` the last inserted text and `":` the last command line, which
+///   can't be written to, like `"%` the file name, which the editor knows
+#[derive(Default)]
+pub struct Registers {
+    unnamed: Register,
+    numbered: [Register; 10],
+    small_delete: Register,
+    named: [Register; 26],
+    clipboard: Register,
+    primary: Register,
+    last_inserted: String,
+    last_command: String,
+}
+
+impl Registers {
+    /// Whether `name` is a register, `"` is the unnamed one
+    pub const fn is_valid(name: char) -> bool {
+        matches!(
+            name,
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '"' | '-' | '_' | '+' | '*' | '.' | ':' | '%'
+        )
+    }
+
+    pub const fn is_read_only(name: char) -> bool {
+        matches!(name, '.' | ':' | '%')
+    }
+
+    /// The register `name`, the unnamed one if `None`. The `"%` register is
+    /// left to the editor.
+    pub fn get(&self, name: Option<char>) -> Option<Register> {
+        let register = match name {
+            None | Some('"') => self.unnamed.clone(),
+            Some(name @ ('a'..='z' | 'A'..='Z')) => {
+                self.named.get(Self::letter_index(name))?.clone()
+            }
+            Some(digit @ '0'..='9') => self.numbered.get(Self::digit_index(digit))?.clone(),
+            Some('-') => self.small_delete.clone(),
+            Some('+') => self.clipboard.clone(),
+            Some('*') => self.primary.clone(),
+            Some('.') => Register::charwise(self.last_inserted.clone()),
+            Some(':') => Register::charwise(self.last_command.clone()),
+            Some(_) => return None,
+        };
+        Some(register)
+    }
+
+    /// Keeps yanked text in `"0`, or in the register `name`. Yanking
+    /// nothing changes no register.
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        if register.text.is_empty() {
+            return;
+        }
+        match name {
+            None | Some('"') => {
+                self.numbered[0] = register.clone();
+                self.unnamed = register;
+            }
+            Some(name) => self.write(name, register),
+        }
+    }
+
+    /// Keeps deleted text in `"1`, moving the older deletes up to `"9`, also
+    /// when it goes to the register `name`. Without `name` a delete within a
+    /// line goes to `"-` instead. Deleting nothing changes no register, nor
+    /// does deleting into `"_`.
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        if register.text.is_empty() || name == Some('_') {
+            return;
+        }
+        let unnamed = matches!(name, None | Some('"'));
+        if register.kind == RegisterKind::Linewise || register.text.contains('\n') {
+            if let Some(deletes) = self.numbered.get_mut(1..) {
+                deletes.rotate_right(1);
+            }
+            self.numbered[1] = register.clone();
+        } else if unnamed {
+            self.small_delete = register.clone();
+        }
+        match name {
+            Some(name) if !unnamed => self.write(name, register),
+            _ => self.unnamed = register,
+        }
+    }
+
+    pub fn set_last_inserted(&mut self, text: String) {
+        self.last_inserted = text;
+    }
+
+    pub fn set_last_command(&mut self, text: String) {
+        self.last_command = text;
+    }
+
+    /// The registers that aren't empty, in the order `:registers` lists them
+    pub fn list(&self) -> Vec<(char, Register)> {
+        ['"']
+            .into_iter()
+            .chain('0'..='9')
+            .chain('a'..='z')
+            .chain(['-', '*', '+', '.', ':'])
+            .filter_map(|name| Some((name, self.get(Some(name))?)))
+            .filter(|(_, register)| !register.text.is_empty())
+            .collect()
+    }
+
+    /// Keeps the keys of a macro recorded with `q`, as text. Unlike a yank
+    /// it leaves the unnamed register as it is, unless recorded into.
+    pub fn record(&mut self, name: char, keys: String) {
+        let register = Register::charwise(keys);
+        if name == '"' {
+            self.unnamed = register;
+        } else {
+            self.store(name, register);
+        }
+    }
+
+    /// Writes to a register by name, which the unnamed register then shows too
+    fn write(&mut self, name: char, register: Register) {
+        if let Some(written) = self.store(name, register) {
+            self.unnamed = written;
+        }
+    }
+
+    /// Writes to a register by name, or appends to it for `"A` to `"Z`.
+    /// Returns what the register then holds, `None` if it can't be written.
+    fn store(&mut self, name: char, register: Register) -> Option<Register> {
+        let stored = match name {
+            'a'..='z' | 'A'..='Z' => self.named.get_mut(Self::letter_index(name)),
+            '0'..='9' => self.numbered.get_mut(Self::digit_index(name)),
+            '-' => Some(&mut self.small_delete),
+            '+' => Some(&mut self.clipboard),
+            '*' => Some(&mut self.primary),
+            _ => None,
+        }?;
+        if name.is_ascii_uppercase() {
+            stored.append(register);
+        } else {
+            *stored = register;
+        }
+        Some(stored.clone())
+    }
+
+    fn letter_index(name: char) -> usize {
+        u8::try_from(name.to_ascii_lowercase())
+            .map_or(0, |byte| usize::from(byte.saturating_sub(b'a')))
+    }
+
+    fn digit_index(digit: char) -> usize {
+        digit
+            .to_digit(10)
+            .and_then(|digit| usize::try_from(digit).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Register {
+        Register {
+            text: text.to_string(),
+            kind: RegisterKind::Linewise,
+        }
+    }
+
+    fn text(registers: &Registers, name: char) -> String {
+        registers.get(Some(name)).unwrap_or_default().text
+    }
+
+    #[test]
+    fn deleted_lines_move_up_the_numbered_registers() {
+        let mut registers = Registers::default();
+        for number in 1..=10 {
+            registers.delete(None, lines(&format!("{number}\n")));
+        }
+        assert_eq!(text(&registers, '1'), "10\n");
+        assert_eq!(text(&registers, '2'), "9\n");
+        assert_eq!(text(&registers, '9'), "2\n");
+        assert_eq!(text(&registers, '"'), "10\n");
+
+        // Into a named register the delete still goes to `"1`
+        registers.delete(Some('a'), lines("named\n"));
+        assert_eq!(text(&registers, '1'), "named\n");
+        assert_eq!(text(&registers, '2'), "10\n");
+        assert_eq!(text(&registers, 'a'), "named\n");
+    }
+
+    #[test]
+    fn small_deletes_and_yanks_leave_the_numbered_registers() {
+        let mut registers = Registers::default();
+        registers.delete(None, lines("line\n"));
+        registers.delete(None, Register::charwise(String::from("word")));
+        registers.yank(None, Register::charwise(String::from("yanked")));
+        assert_eq!(text(&registers, '1'), "line\n");
+        assert_eq!(text(&registers, '-'), "word");
+        assert_eq!(text(&registers, '0'), "yanked");
+        assert_eq!(text(&registers, '"'), "yanked");
+    }
+
+    #[test]
+    fn nothing_and_the_black_hole_change_no_register() {
+        let mut registers = Registers::default();
+        registers.yank(None, Register::charwise(String::from("kept")));
+        registers.delete(None, Register::default());
+        registers.delete(Some('_'), lines("gone\n"));
+        registers.yank(Some('a'), Register::default());
+        assert_eq!(text(&registers, '"'), "kept");
+        assert_eq!(text(&registers, '1'), "");
+        assert!(
+            registers
+                .list()
+                .iter()
+                .all(|(name, _)| ['"', '0'].contains(name))
+        );
+    }
+
+    #[test]
+    fn uppercase_names_append() {
+        let mut registers = Registers::default();
+        registers.yank(Some('a'), Register::charwise(String::from("foo")));
+        registers.yank(Some('A'), Register::charwise(String::from("bar")));
+        assert_eq!(text(&registers, 'a'), "foobar");
+
+        registers.yank(Some('A'), lines("line\n"));
+        let appended = registers.get(Some('a')).unwrap_or_default();
+        assert_eq!(appended.text, "foobar\nline\n");
+        assert_eq!(appended.kind, RegisterKind::Linewise);
+        assert_eq!(text(&registers, '"'), "foobar\nline\n");
+    }
+}
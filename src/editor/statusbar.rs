@@ -38,12 +38,24 @@ impl UIComponent for StatusBar {
     fn draw(&mut self, origin_y: usize) -> Result<(), std::io::Error> {
         let line_count = self.current_status.line_count_to_string();
         let modified_indicator = self.current_status.modified_indicator_to_string();
+        let hex_indicator = if self.current_status.is_hex {
+            "[hex] "
+        } else {
+            ""
+        };
         let beginning = format!(
-            "{} - {line_count} {modified_indicator}",
+            "{} {} - {line_count} {hex_indicator}{modified_indicator}",
+            self.current_status.buffer_indicator_to_string(),
             self.current_status.file_name,
         );
 
-        let position_indicator = self.current_status.position_indicator_to_string();
+        let position_indicator = format!(
+            "{}{}{} | {}",
+            self.current_status.recording_to_string(),
+            self.current_status.pending_keys_to_string(),
+            self.current_status.file_format,
+            self.current_status.position_indicator_to_string()
+        );
         let remainder_len = self.size.width.saturating_sub(beginning.len());
         let status = format!("{beginning}{position_indicator:>remainder_len$}");
 
@@ -0,0 +1,65 @@
+use std::io::Error;
+
+use unicode_width::UnicodeWidthStr;
+
+use super::{
+    terminal::{Position, Size, Terminal},
+    uicomponent::UIComponent,
+};
+
+/// Row at the top listing the tab pages, with the current one not inverted
+#[derive(Default)]
+pub struct TabLine {
+    labels: Vec<String>,
+    active: usize,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl TabLine {
+    pub fn update_tabs(&mut self, labels: Vec<String>, active: usize) {
+        if labels == self.labels && active == self.active {
+            return;
+        }
+
+        self.labels = labels;
+        self.active = active;
+        self.set_needs_redraw(true);
+    }
+}
+
+impl UIComponent for TabLine {
+    fn set_needs_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+        Terminal::print_inverted_row(origin_y, "")?;
+
+        let mut col = 0;
+        for (index, label) in self.labels.iter().enumerate() {
+            let label = format!(" {} {label} ", index.saturating_add(1));
+            let width = label.width().min(self.size.width.saturating_sub(col));
+            if width == 0 {
+                break;
+            }
+            let position = Position { col, row: origin_y };
+            if index == self.active {
+                Terminal::print_at(position, &format!("{label:width$.width$}"))?;
+            } else {
+                Terminal::print_inverted_at(position, &label, width)?;
+            }
+            col = col.saturating_add(width);
+        }
+
+        Ok(())
+    }
+}
@@ -33,9 +33,9 @@ pub struct Terminal;
 
 /// Represents the Terminal
 /// Edge Case:
-///     - Platform of `usize` < `ul6` -> Only spans over at most usize::MAX
+///     - Platform of `usize` < `ul6` -> Only spans over at most `usize::MAX`
 ///     or `u16::size` rows/columns
-///     - Each size returned truncates to min(usize::MAX, u16::MAX)
+///     - Each size returned truncates to `min(usize::MAX, u16::MAX)`
 ///     - caret out of bound will be truncated
 impl Terminal {
     pub fn terminate() -> Result<(), Error> {
@@ -112,6 +112,25 @@ impl Terminal {
         )
     }
 
+    /// Prints at `position` without clearing the rest of the row
+    pub fn print_at(position: Position, text: &str) -> Result<(), Error> {
+        Self::move_caret_to(position)?;
+        Self::print(text)
+    }
+
+    /// Prints `text` inverted, cut or padded to `width` columns
+    pub fn print_inverted_at(position: Position, text: &str, width: usize) -> Result<(), Error> {
+        Self::print_at(
+            position,
+            &format!(
+                "{}{:width$.width$}{}",
+                Attribute::Reverse,
+                text,
+                Attribute::Reset
+            ),
+        )
+    }
+
     pub fn clear_screen() -> Result<(), Error> {
         Self::queue_command(Clear(ClearType::All))
     }
@@ -142,6 +161,17 @@ impl Terminal {
         Ok(())
     }
 
+    /// Sets the clipboard, or the primary selection, of the terminal with
+    /// an OSC 52 escape sequence. Also works over ssh.
+    pub fn copy_to_clipboard(text: &str, primary: bool) -> Result<(), Error> {
+        let selection = if primary { 'p' } else { 'c' };
+        Self::print(&format!(
+            "\x1b]52;{selection};{}\x07",
+            base64(text.as_bytes())
+        ))?;
+        Self::execute()
+    }
+
     fn queue_command<T: Command>(command: T) -> Result<(), Error> {
         queue!(stdout(), command)?;
         Ok(())
@@ -152,3 +182,27 @@ impl Terminal {
         Ok(())
     }
 }
+
+/// Standard base64 with padding, as OSC 52 expects
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().saturating_mul(4).div_ceil(3));
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .chain([0, 0].iter())
+            .take(3)
+            .fold(0_u32, |group, &byte| {
+                group.wrapping_shl(8) | u32::from(byte)
+            });
+        for (index, shift) in [18, 12, 6, 0].into_iter().enumerate() {
+            if index > chunk.len() {
+                encoded.push('=');
+                continue;
+            }
+            let sextet = usize::try_from(group.wrapping_shr(shift) & 0x3f).unwrap_or_default();
+            encoded.push(ALPHABET.get(sextet).copied().map_or('=', char::from));
+        }
+    }
+    encoded
+}
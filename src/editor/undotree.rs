@@ -0,0 +1,97 @@
+use std::io::Error;
+use std::time::SystemTime;
+
+use super::{
+    terminal::{Size, Terminal},
+    uicomponent::UIComponent,
+    view::UndoTreeEntry,
+};
+
+/// Panel listing the states of the undo tree, one per row, with the
+/// current state marked by `*`.
+#[derive(Default)]
+pub struct UndoTree {
+    entries: Vec<UndoTreeEntry>,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl UndoTree {
+    pub fn update_entries(&mut self, entries: Vec<UndoTreeEntry>) {
+        if entries == self.entries {
+            return;
+        }
+
+        self.entries = entries;
+        self.set_needs_redraw(true);
+    }
+
+    fn format_entry(entry: &UndoTreeEntry, now: SystemTime) -> String {
+        let marker = if entry.is_current { '*' } else { 'o' };
+        let saved = if entry.is_saved { " (saved)" } else { "" };
+        let age = if entry.seq == 0 {
+            String::from("original")
+        } else {
+            Self::format_age(now.duration_since(entry.time).unwrap_or_default().as_secs())
+        };
+
+        format!("{}{marker} {}  {age}{saved}", entry.graph, entry.seq)
+    }
+
+    fn format_age(seconds: u64) -> String {
+        match seconds {
+            0..60 => format!("{seconds}s ago"),
+            60..3600 => format!("{}m ago", seconds.saturating_div(60)),
+            3600..86400 => format!("{}h ago", seconds.saturating_div(3600)),
+            _ => format!("{}d ago", seconds.saturating_div(86400)),
+        }
+    }
+}
+
+impl UIComponent for UndoTree {
+    fn set_needs_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+        let Size { height, width } = self.size;
+        if height == 0 {
+            return Ok(());
+        }
+
+        Terminal::print_inverted_row(origin_y, " Undo tree (g- / g+ to travel)")?;
+
+        // Keep the current state in the middle of the panel when possible
+        let rows = height.saturating_sub(1);
+        let current = self
+            .entries
+            .iter()
+            .position(|entry| entry.is_current)
+            .unwrap_or_default();
+        #[allow(clippy::integer_division)]
+        let first = current
+            .saturating_sub(rows / 2)
+            .min(self.entries.len().saturating_sub(rows));
+
+        let now = SystemTime::now();
+        for row in 0..rows {
+            let text = self
+                .entries
+                .get(first.saturating_add(row))
+                .map(|entry| Self::format_entry(entry, now))
+                .unwrap_or_default();
+            let text: String = text.chars().take(width).collect();
+            Terminal::print_row(origin_y.saturating_add(row).saturating_add(1), &text)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,41 +1,99 @@
 use super::DocumentStatus;
 use super::{
-    Mode, NAME, VERSION,
-    command::{Direction, EditorCommand, InsertCommand, NormalCommand, VisualCommand},
+    Mode, NAME, Options, VERSION,
+    command::{
+        Direction, EditorCommand, InsertCommand, Motion, NormalCommand, Operator, Target, TimeSpan,
+        VisualCommand, VisualKind,
+    },
+    register::Register,
     terminal::{Position, Size, Terminal},
     uicomponent::UIComponent,
 };
-use std::{cmp::min, io::Error};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, cmp::min, io::Error, ops::Range, path::Path, rc::Rc};
+use unicode_width::UnicodeWidthStr;
 
 mod buffer;
+mod encoding;
+mod fileio;
+mod graphemes;
+mod hex;
+mod history;
+mod jumplist;
 mod line;
+mod marks;
+mod motion;
+mod operator;
 mod text_fragment;
-
-use buffer::Buffer;
+mod textobject;
+mod undofile;
+mod visual;
+
+pub use buffer::Buffer;
+use hex::HexView;
+pub use history::UndoTreeEntry;
+pub use jumplist::Jump;
+use jumplist::JumpList;
 use line::Line;
+use visual::{BlockInsert, Motions, Selection};
+
+/// A buffer can be shown by several views and listed at the same time
+pub type SharedBuffer = Rc<RefCell<Buffer>>;
 
 #[derive(Default)]
 pub struct View {
     scroll_offset: Position,
     text_location: Location,
-    buffer: Buffer,
+    buffer: SharedBuffer,
+    /// Shows the buffer as bytes instead of text when set
+    hex: Option<HexView>,
+    /// The selection while in Visual mode
+    selection: Option<Selection>,
+    /// Pending after `c` on a block until Insert mode is left
+    block_insert: Option<BlockInsert>,
+    jumps: JumpList,
+    /// Revision of the buffer when the view was last drawn
+    seen_revision: u64,
+    /// Screen column of the left edge, the row is given when rendering
+    origin_col: usize,
     needs_redraw: bool,
     size: Size,
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     pub grapheme_index: usize,
     pub line_index: usize,
 }
 
+/// Where a view was in a buffer, to come back to the same place when the
+/// buffer is shown again
+#[derive(Clone, Copy, Default)]
+pub struct ViewState {
+    text_location: Location,
+    scroll_offset: Position,
+}
+
+impl ViewState {
+    pub const fn line_index(&self) -> usize {
+        self.text_location.line_index
+    }
+}
+
 impl View {
     // Start Region: Handle Editor Command
     pub fn handle_command(&mut self, command: EditorCommand) {
         match command {
-            EditorCommand::Save => self.save(),
-            EditorCommand::Normal(normal_command) => self.handle_normal_command(normal_command),
-            EditorCommand::Visual(visual_command) => self.handle_visual_command(visual_command),
+            // Leaving Insert mode closes the current undo step
+            EditorCommand::Esc => {
+                if let Some(insert) = self.block_insert.take() {
+                    insert.finish(&mut self.buffer.borrow_mut(), self.text_location);
+                }
+                self.buffer.borrow_mut().commit();
+            }
+            EditorCommand::Normal(normal_command) => {
+                self.handle_normal_command(normal_command, None);
+            }
             EditorCommand::Insert(insert_command) => self.handle_insert_command(insert_command),
             _ => {
                 // Other cases should have been handled from the editor.rs
@@ -43,16 +101,69 @@ impl View {
         }
     }
 
-    fn handle_normal_command(&mut self, command: NormalCommand) {
+    /// Runs the command `count` times, or for the motions that take a line
+    /// number, with `count` as the line. Returns `false` if it is a motion
+    /// that can't move the cursor, like `j` on the last line.
+    pub fn handle_normal_command(&mut self, command: NormalCommand, count: Option<usize>) -> bool {
+        let times = count.unwrap_or(1);
+        let mut moved = true;
         match command {
-            NormalCommand::Move(direction) => self.move_text_location(&direction),
+            NormalCommand::Move(direction) => {
+                let from = self.text_location;
+                for _ in 0..times {
+                    self.move_text_location(direction);
+                }
+                moved = self.hex.is_some() || self.text_location != from;
+            }
+            NormalCommand::Motion(motion) => {
+                let from = self.jump();
+                moved = self.apply_motion(motion, count);
+                if moved && motion.is_jump() {
+                    self.push_jump(from);
+                }
+            }
+            NormalCommand::SetMark(name) => self.set_mark(name),
+            // Operators and putting text need a register, `.` replays
+            // commands and jumps may go to other buffers, the editor takes
+            // care of them
+            NormalCommand::Operate(..)
+            | NormalCommand::Put { .. }
+            | NormalCommand::Repeat
+            | NormalCommand::OlderJump
+            | NormalCommand::NewerJump => {}
+            NormalCommand::Undo => {
+                for _ in 0..times {
+                    self.undo();
+                }
+            }
+            NormalCommand::Redo => {
+                for _ in 0..times {
+                    self.redo();
+                }
+            }
+            NormalCommand::Earlier(span) => {
+                for _ in 0..times {
+                    self.travel_history(span, false);
+                }
+            }
+            NormalCommand::Later(span) => {
+                for _ in 0..times {
+                    self.travel_history(span, true);
+                }
+            }
+        }
+        // The selection follows the cursor
+        if self.selection.is_some() {
+            self.needs_redraw = true;
         }
+        moved
     }
-    // TODO: To be implemented when we have visual command
-    #[allow(unused_variables)]
-    fn handle_visual_command(&mut self, command: VisualCommand) {}
 
     fn handle_insert_command(&mut self, command: InsertCommand) {
+        if self.hex.is_some() {
+            self.handle_hex_insert_command(command);
+            return;
+        }
         match command {
             InsertCommand::Char(c) => {
                 self.insert_char(c);
@@ -71,24 +182,132 @@ impl View {
     // Start Region: Misc
 
     pub fn get_status(&self) -> DocumentStatus {
+        let (total_lines, current_line_index) = match &self.hex {
+            Some(hex) => (hex.row_count(), hex.cursor_row()),
+            None => (self.buffer.borrow().height(), self.text_location.line_index),
+        };
         DocumentStatus {
-            total_lines: self.buffer.height(),
-            current_line_index: self.text_location.line_index,
-            is_modified: self.buffer.dirty,
-            file_name: format!("{}", self.buffer.file_info),
+            total_lines,
+            current_line_index,
+            is_hex: self.hex.is_some(),
+            is_modified: self.buffer.borrow().is_dirty(),
+            file_name: format!("{}", self.buffer.borrow().file_info),
+            file_format: self.buffer.borrow().file_info.format,
             mode: Mode::Normal,
+            ..DocumentStatus::default()
         }
     }
 
-    pub fn load(&mut self, file_name: &str) {
-        if let Ok(buffer) = Buffer::load(file_name) {
-            self.buffer = buffer;
-            self.set_needs_redraw(true);
+    pub fn undo_tree(&self) -> Vec<UndoTreeEntry> {
+        self.buffer.borrow().undo_tree()
+    }
+
+    pub fn buffer(&self) -> &SharedBuffer {
+        &self.buffer
+    }
+
+    pub const fn size(&self) -> Size {
+        self.size
+    }
+
+    pub const fn set_origin_col(&mut self, col: usize) {
+        self.origin_col = col;
+    }
+
+    /// Catches up with changes made to the buffer through other views
+    pub fn sync_with_buffer(&mut self) {
+        let revision = self.buffer.borrow().revision();
+        if revision != self.seen_revision {
+            self.seen_revision = revision;
+            self.snap_to_valid_line();
+            self.snap_to_valid_grapheme();
+            self.refresh_hex();
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn state(&self) -> ViewState {
+        ViewState {
+            text_location: self.text_location,
+            scroll_offset: self.scroll_offset,
         }
     }
 
-    pub fn save(&mut self) {
-        let _ = self.buffer.save();
+    /// Shows `buffer` at the place described by `state`
+    pub fn set_buffer(&mut self, buffer: SharedBuffer, state: ViewState) {
+        self.buffer.borrow_mut().commit();
+        self.buffer = buffer;
+        self.hex = None;
+        self.text_location = state.text_location;
+        self.scroll_offset = state.scroll_offset;
+        // The buffer may have changed since the state was saved
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_text_location_into_view();
+        self.set_needs_redraw(true);
+    }
+
+    /// Writes the buffer to its file and returns the number of bytes written
+    pub fn save(&mut self, options: &Options) -> Result<usize, Error> {
+        self.buffer.borrow_mut().save(options.backup)
+    }
+
+    pub fn save_as(&mut self, file_name: &str, options: &Options) -> Result<usize, Error> {
+        let bytes = self
+            .buffer
+            .borrow_mut()
+            .save_as(file_name, options.backup)?;
+        self.set_needs_redraw(true);
+        Ok(bytes)
+    }
+
+    pub fn write_to(&self, file_name: &str, options: &Options) -> Result<usize, Error> {
+        self.buffer
+            .borrow()
+            .write_to(Path::new(file_name), options.backup)
+    }
+
+    pub fn file_name(&self) -> Option<String> {
+        self.buffer
+            .borrow()
+            .file_info
+            .path
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.buffer.borrow().is_dirty()
+    }
+
+    /// Applies a `:set` argument for a buffer local option. Returns `None`
+    /// when the option is not buffer local.
+    pub fn set_buffer_option(&mut self, argument: &str) -> Option<Result<Option<String>, String>> {
+        self.buffer.borrow_mut().file_info.format.set(argument)
+    }
+
+    /// Switches between showing the text and the bytes of the buffer.
+    /// Returns whether the bytes are shown now.
+    pub fn toggle_hex(&mut self) -> Result<bool, Error> {
+        self.buffer.borrow_mut().commit();
+        self.hex = match self.hex {
+            Some(_) => None,
+            None => Some(HexView::from(self.buffer.borrow().to_bytes()?)),
+        };
+        self.scroll_offset = Position::default();
+        self.scroll_text_location_into_view();
+        self.set_needs_redraw(true);
+        Ok(self.hex.is_some())
+    }
+
+    /// Reloads the bytes shown in hex from the buffer after it changed
+    fn refresh_hex(&mut self) {
+        if let Some(hex) = &mut self.hex {
+            // The buffer was representable before, undoing can't change that
+            if let Ok(bytes) = self.buffer.borrow().to_bytes() {
+                hex.set_bytes(bytes);
+            }
+        }
     }
 
     fn build_welcome_message(width: usize) -> String {
@@ -109,8 +328,51 @@ impl View {
 
     // Start Region: Rendering
 
-    pub fn render_line(at: usize, line_text: &str) {
-        let result = Terminal::print_row(at, line_text);
+    /// Prints a row of the view, padded to its width so that whatever was
+    /// shown before is overwritten without touching the columns beside it
+    pub fn render_line(&self, at: usize, line_text: &str) {
+        let padding = self.size.width.saturating_sub(line_text.width());
+        let position = Position {
+            col: self.origin_col,
+            row: at,
+        };
+        let result = Terminal::print_at(position, &format!("{line_text}{:padding$}", ""));
+        debug_assert!(result.is_ok(), "Failed to render line");
+    }
+
+    /// Prints a row of the view with the `selected` columns inverted
+    fn render_selected_line(
+        &self,
+        at: usize,
+        line: &Line,
+        visible: Range<usize>,
+        selected: Range<usize>,
+    ) {
+        let start = selected.start.clamp(visible.start, visible.end);
+        let end = selected.end.clamp(start, visible.end);
+        let mut position = Position {
+            col: self.origin_col,
+            row: at,
+        };
+        let before = line.get_visible_graphemes(visible.start..start);
+        let mut result = Terminal::print_at(position, &before);
+
+        position.col = position
+            .col
+            .saturating_add(start.saturating_sub(visible.start));
+        let inside = line.get_visible_graphemes(start..end);
+        result = result.and_then(|()| {
+            Terminal::print_inverted_at(position, &inside, end.saturating_sub(start))
+        });
+
+        position.col = position.col.saturating_add(end.saturating_sub(start));
+        let after = line.get_visible_graphemes(end..visible.end);
+        let padding = visible
+            .end
+            .saturating_sub(end)
+            .saturating_sub(after.width());
+        result =
+            result.and_then(|()| Terminal::print_at(position, &format!("{after}{:padding$}", "")));
         debug_assert!(result.is_ok(), "Failed to render line");
     }
 
@@ -169,11 +431,15 @@ impl View {
     }
 
     fn text_location_to_position(&self) -> Position {
+        if let Some(hex) = &self.hex {
+            return Position {
+                col: hex.cursor_col(),
+                row: hex.cursor_row(),
+            };
+        }
         let row = self.text_location.line_index;
 
-        let col = self.buffer.lines.get(row).map_or(0, |line| {
-            line.width_until(self.text_location.grapheme_index)
-        });
+        let col = self.buffer.borrow().width_until(self.text_location);
 
         Position { col, row }
     }
@@ -183,9 +449,15 @@ impl View {
     // Start Region: Text Location Movement
 
     #[allow(clippy::arithmetic_side_effects)]
-    fn move_text_location(&mut self, direction: &Direction) {
+    fn move_text_location(&mut self, direction: Direction) {
         let Size { height, .. } = self.size;
 
+        if let Some(hex) = &mut self.hex {
+            hex.move_cursor(direction, height.saturating_sub(1));
+            self.scroll_text_location_into_view();
+            return;
+        }
+
         match direction {
             Direction::Up => self.move_up(1),
             Direction::Down => self.move_down(1),
@@ -199,6 +471,49 @@ impl View {
         self.scroll_text_location_into_view();
     }
 
+    /// Returns whether the cursor could move, always in hex mode
+    pub fn apply_motion(&mut self, motion: Motion, count: Option<usize>) -> bool {
+        let mut moved = true;
+        if let Some(hex) = &mut self.hex {
+            // Only the line motions make sense on bytes
+            match motion {
+                Motion::LineStart | Motion::FirstNonBlank => hex.move_cursor(Direction::Home, 0),
+                Motion::LineEnd => hex.move_cursor(Direction::End, 0),
+                Motion::FirstLine | Motion::LastLine if count.is_some() => {
+                    hex.goto_row(count.unwrap_or_default().saturating_sub(1));
+                }
+                Motion::FirstLine => hex.goto_row(0),
+                Motion::LastLine => hex.goto_row(hex.row_count().saturating_sub(1)),
+                _ => {}
+            }
+        } else {
+            let target = motion::target(&self.buffer.borrow(), self.text_location, motion, count);
+            match target {
+                Some(target) => self.text_location = target,
+                None => moved = false,
+            }
+        }
+        self.scroll_text_location_into_view();
+        moved
+    }
+
+    /// Moves to the start of the 1-based `line_number`, clamped to the buffer
+    pub fn goto_line(&mut self, line_number: usize) {
+        self.record_jump();
+        if let Some(hex) = &mut self.hex {
+            hex.goto_row(line_number.saturating_sub(1));
+            self.scroll_text_location_into_view();
+            return;
+        }
+        self.text_location = Location {
+            line_index: line_number
+                .saturating_sub(1)
+                .min(self.buffer.borrow().height().saturating_sub(1)),
+            grapheme_index: 0,
+        };
+        self.scroll_text_location_into_view();
+    }
+
     fn move_up(&mut self, step: usize) {
         self.text_location.line_index = self.text_location.line_index.saturating_sub(step);
         self.snap_to_valid_grapheme();
@@ -221,16 +536,14 @@ impl View {
         }
     }
 
-    #[allow(clippy::arithmetic_side_effects)]
     fn move_right(&mut self) {
-        let line_width = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+        let next = Location {
+            grapheme_index: self.text_location.grapheme_index.saturating_add(1),
+            ..self.text_location
+        };
 
-        if self.text_location.grapheme_index < line_width {
-            self.text_location.grapheme_index += 1;
+        if self.buffer.borrow().clamp_to_line(next) == next {
+            self.text_location = next;
         } else {
             // Vim like movement
             self.move_to_start_of_line();
@@ -245,71 +558,350 @@ impl View {
     fn move_to_end_of_line(&mut self) {
         self.text_location.grapheme_index = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+            .borrow()
+            .grapheme_count(self.text_location.line_index);
     }
 
     fn snap_to_valid_grapheme(&mut self) {
-        self.text_location.grapheme_index = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, |line| {
-                min(line.grapheme_count(), self.text_location.grapheme_index)
-            });
+        self.text_location = self.buffer.borrow().clamp_to_line(self.text_location);
     }
 
     fn snap_to_valid_line(&mut self) {
-        self.text_location.line_index = min(self.text_location.line_index, self.buffer.height());
+        self.text_location.line_index =
+            min(self.text_location.line_index, self.buffer.borrow().height());
+    }
+
+    /// Moves to `location` in the buffer, as far as it still goes
+    pub fn goto(&mut self, location: Location) {
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_text_location_into_view();
     }
 
     // End Region: Text Location Movement
 
-    // Start Region: Text Mutation
+    // Start Region: Marks and Jumps
 
-    fn insert_char(&mut self, character: char) {
-        let old_len = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+    /// `m{mark}`, marks the cursor position. `` m` `` is the same as `m'`.
+    pub fn set_mark(&mut self, name: char) {
+        let name = if name == '`' { '\'' } else { name };
+        self.buffer.borrow_mut().set_mark(name, self.text_location);
+    }
 
-        self.buffer.insert_char(character, self.text_location);
+    /// The cursor position as a place to jump back to
+    fn jump(&self) -> Jump {
+        Jump {
+            buffer: Rc::clone(&self.buffer),
+            location: self.text_location,
+        }
+    }
 
-        let new_len = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+    /// Keeps the cursor position in the jump list before jumping away
+    pub fn record_jump(&mut self) {
+        self.push_jump(self.jump());
+    }
+
+    /// Keeps a place jumped from in the jump list and as the `'` mark
+    fn push_jump(&mut self, from: Jump) {
+        from.buffer.borrow_mut().set_mark('\'', from.location);
+        self.jumps.push(from);
+    }
+
+    /// Ctrl-O, the place `count` jumps back
+    pub fn older_jump(&mut self, count: usize) -> Option<Jump> {
+        let current = self.jump();
+        self.jumps.back(current, count)
+    }
+
+    /// Ctrl-I, the place `count` jumps forth again
+    pub fn newer_jump(&mut self, count: usize) -> Option<Jump> {
+        self.jumps.forward(count)
+    }
+
+    // End Region: Marks and Jumps
+
+    // Start Region: Visual Mode
+
+    /// Starts selecting at the cursor. Bytes can't be selected in hex mode.
+    pub fn start_selection(&mut self, kind: VisualKind) -> bool {
+        if self.hex.is_some() {
+            return false;
+        }
+        self.selection = Some(Selection {
+            anchor: self.text_location,
+            kind,
+        });
+        self.needs_redraw = true;
+        true
+    }
+
+    pub fn selection_kind(&self) -> Option<VisualKind> {
+        self.selection.map(|selection| selection.kind)
+    }
+
+    /// The kind of selection and the motions selecting as much text again,
+    /// see `Selection::motions`
+    pub fn selection_motions(&self) -> Option<(VisualKind, Motions)> {
+        self.selection
+            .map(|selection| (selection.kind, selection.motions(self.text_location)))
+    }
+
+    pub fn set_selection_kind(&mut self, kind: VisualKind) {
+        if let Some(selection) = &mut self.selection {
+            selection.kind = kind;
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn end_selection(&mut self) {
+        if self.selection.take().is_some() {
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Moves the cursor to the other end of the selection, `o`
+    pub fn swap_selection_ends(&mut self) {
+        if let Some(selection) = &mut self.selection {
+            std::mem::swap(&mut selection.anchor, &mut self.text_location);
+            self.scroll_text_location_into_view();
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Applies an operator, `J` or `r` to the selection, keeping the text
+    /// deleted or yanked in `register`, and ends the selection. Returns
+    /// whether it succeeded.
+    pub fn apply_to_selection(
+        &mut self,
+        command: VisualCommand,
+        count: Option<usize>,
+        register: &mut Register,
+        options: &Options,
+    ) -> bool {
+        let Some(selection) = self.selection else {
+            return false;
+        };
+        let cursor = self.text_location;
+        let mut buffer = self.buffer.borrow_mut();
+        let block = selection.kind == VisualKind::Block;
+        let spans = if block {
+            selection.block(&buffer, cursor)
+        } else {
+            vec![selection.span(&buffer, cursor)]
+        };
+
+        let location = match command {
+            VisualCommand::Operate(operator) => {
+                // `3>` shifts three times
+                let times = match operator {
+                    Operator::Indent | Operator::Outdent => count.unwrap_or(1),
+                    _ => 1,
+                };
+                let mut location = cursor;
+                for _ in 0..times {
+                    location = match spans.first() {
+                        Some(&span) if !block => operator::apply(
+                            &mut buffer,
+                            span,
+                            operator,
+                            cursor,
+                            register,
+                            options.shiftwidth,
+                        ),
+                        _ => operator::apply_block(
+                            &mut buffer,
+                            &spans,
+                            operator,
+                            cursor,
+                            register,
+                            options.shiftwidth,
+                        ),
+                    };
+                }
+                if block && operator == Operator::Change {
+                    self.block_insert = BlockInsert::new(&buffer, &spans);
+                }
+                Some(location)
+            }
+            VisualCommand::Join => spans.first().zip(spans.last()).and_then(|(first, last)| {
+                let first_line = first.start.line_index;
+                let last_line = last.last_line().max(first_line.saturating_add(1));
+                operator::join(&mut buffer, first_line, last_line, cursor)
+            }),
+            VisualCommand::Replace(replacement) => {
+                Some(operator::replace(&mut buffer, &spans, replacement, cursor))
+            }
+            VisualCommand::Select(_) | VisualCommand::SwapEnds => None,
+        };
+        drop(buffer);
+        self.end_selection();
 
-        let grapheme_delta = new_len.saturating_sub(old_len);
+        let Some(location) = location else {
+            return false;
+        };
+        self.text_location = location;
+        self.scroll_text_location_into_view();
+        true
+    }
+
+    // End Region: Visual Mode
+
+    // Start Region: Text Mutation
+
+    /// Applies `operator` to the text `target` covers, keeping the text it
+    /// deletes or yanks in `register`. Returns whether it succeeded.
+    pub fn apply_operator(
+        &mut self,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+        register: &mut Register,
+        options: &Options,
+    ) -> bool {
+        if self.hex.is_some() {
+            return false;
+        }
+        let from = self.text_location;
+        let Some(span) = operator::span(&self.buffer.borrow(), from, operator, target, count)
+        else {
+            return false;
+        };
+        let location = operator::apply(
+            &mut self.buffer.borrow_mut(),
+            span,
+            operator,
+            from,
+            register,
+            options.shiftwidth,
+        );
+        if operator == Operator::Yank {
+            // The changes set `[` and `]` as they make them
+            let mut buffer = self.buffer.borrow_mut();
+            let last = buffer.char_offset(span.end).saturating_sub(1);
+            let last = buffer.location_of(last);
+            buffer.set_mark('[', span.start);
+            buffer.set_mark(']', last);
+        }
+        self.text_location = location;
+        self.scroll_text_location_into_view();
+        true
+    }
 
-        if grapheme_delta > 0 {
-            self.move_text_location(&Direction::Right);
+    /// Puts the text of `register` after the cursor, or before it with
+    /// `before`, `count` times
+    pub fn put(&mut self, register: &Register, before: bool, count: Option<usize>) -> bool {
+        if self.hex.is_some() {
+            return false;
         }
+        let location = operator::put(
+            &mut self.buffer.borrow_mut(),
+            register,
+            self.text_location,
+            before,
+            count.unwrap_or(1),
+        );
+        let Some(location) = location else {
+            return false;
+        };
+        self.text_location = location;
+        self.scroll_text_location_into_view();
+        true
+    }
 
+    fn insert_char(&mut self, character: char) {
+        self.text_location = self
+            .buffer
+            .borrow_mut()
+            .insert_char(character, self.text_location);
+        self.scroll_text_location_into_view();
         self.needs_redraw = true;
     }
 
     fn insert_newline(&mut self) {
-        self.buffer.insert_newline(self.text_location);
-        self.move_text_location(&Direction::Right);
+        self.buffer.borrow_mut().insert_newline(self.text_location);
+        self.move_text_location(Direction::Right);
         self.needs_redraw = true;
     }
 
     fn delete(&mut self) {
-        self.buffer.delete(self.text_location);
+        self.buffer.borrow_mut().delete(self.text_location);
         self.needs_redraw = true;
     }
 
     fn backspace(&mut self) {
         if self.text_location.grapheme_index != 0 || self.text_location.line_index != 0 {
-            self.move_text_location(&Direction::Left);
+            self.move_text_location(Direction::Left);
             self.delete();
         }
     }
 
+    /// In hex mode typing overwrites the nibble under the cursor
+    fn handle_hex_insert_command(&mut self, command: InsertCommand) {
+        let Some(hex) = &mut self.hex else {
+            return;
+        };
+        match command {
+            InsertCommand::Char(character) => {
+                let Some(digit) = character.to_digit(16).and_then(|d| u8::try_from(d).ok()) else {
+                    return;
+                };
+                let Some(offset) = hex.overwrite(digit) else {
+                    return;
+                };
+                let mut buffer = self.buffer.borrow_mut();
+                if buffer.set_byte(hex.bytes(), offset, self.text_location) {
+                    // The bytes shown are still the ones saved, no need to
+                    // load them again
+                    self.seen_revision = buffer.revision();
+                }
+                drop(buffer);
+                self.snap_to_valid_line();
+                self.snap_to_valid_grapheme();
+            }
+            InsertCommand::Backspace => hex.move_nibble_left(),
+            InsertCommand::Delete | InsertCommand::Enter => {}
+        }
+        self.scroll_text_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    fn undo(&mut self) {
+        let location = self.buffer.borrow_mut().undo();
+        if let Some(location) = location {
+            self.restore_text_location(location);
+        }
+    }
+
+    fn redo(&mut self) {
+        let location = self.buffer.borrow_mut().redo();
+        if let Some(location) = location {
+            self.restore_text_location(location);
+        }
+    }
+
+    fn travel_history(&mut self, span: TimeSpan, forward: bool) {
+        let location = match span {
+            TimeSpan::Steps(count) => self.buffer.borrow_mut().step_history(count, forward),
+            TimeSpan::Duration(duration) => {
+                self.buffer.borrow_mut().travel_history(duration, forward)
+            }
+        };
+        if let Some(location) = location {
+            self.restore_text_location(location);
+        }
+    }
+
+    /// Moves the cursor back to where a change taken from the history was made
+    fn restore_text_location(&mut self, location: Location) {
+        self.refresh_hex();
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_text_location_into_view();
+        self.needs_redraw = true;
+    }
+
     // End Region: Text Mutation
 }
 
@@ -324,6 +916,7 @@ impl UIComponent for View {
 
     fn set_size(&mut self, size: Size) {
         self.size = size;
+        self.scroll_text_location_into_view();
     }
 
     fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
@@ -337,14 +930,31 @@ impl UIComponent for View {
             let line_idx = current_row
                 .saturating_sub(origin_y)
                 .saturating_add(scroll_top);
-            if let Some(line) = self.buffer.lines.get(line_idx) {
+            if let Some(hex) = &self.hex {
+                // Hex rows are plain ASCII, so columns are byte offsets
+                let row = hex.row(line_idx).unwrap_or_else(|| String::from("~"));
+                let left = self.scroll_offset.col.min(row.len());
+                let right = left.saturating_add(width).min(row.len());
+                self.render_line(current_row, row.get(left..right).unwrap_or_default());
+            } else if let Some(line) = self.buffer.borrow().visible_line(
+                line_idx,
+                self.scroll_offset.col..self.scroll_offset.col.saturating_add(width),
+            ) {
                 let left = self.scroll_offset.col;
                 let right = self.scroll_offset.col.saturating_add(width);
-                Self::render_line(current_row, &line.get_visible_graphemes(left..right));
-            } else if current_row == top_third && self.buffer.is_empty() {
-                Self::render_line(current_row, &Self::build_welcome_message(width));
+                let selected = self.selection.and_then(|selection| {
+                    selection.columns(&self.buffer.borrow(), self.text_location, line_idx)
+                });
+                match selected {
+                    Some(columns) => {
+                        self.render_selected_line(current_row, &line, left..right, columns);
+                    }
+                    None => self.render_line(current_row, &line.get_visible_graphemes(left..right)),
+                }
+            } else if current_row == top_third && self.buffer.borrow().is_empty() {
+                self.render_line(current_row, &Self::build_welcome_message(width));
             } else {
-                Self::render_line(current_row, "~");
+                self.render_line(current_row, "~");
             }
         }
         Ok(())
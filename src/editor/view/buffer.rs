@@ -1,94 +1,708 @@
 use std::{
-    fs::{File, read_to_string},
-    io::{Error, Write},
+    borrow::Cow,
+    fs,
+    io::{Error, ErrorKind, Write},
+    mem,
+    ops::Range,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
 };
 
-use super::{Location, line::Line};
+use ropey::{Rope, RopeSlice, iter::Chars};
 
-use crate::editor::fileinfo::FileInfo;
+use super::{
+    Location, encoding, fileio,
+    graphemes::{Checkpoint, Checkpoints, Graphemes, PreviousGraphemes, Walk},
+    history::{Edit, History, UndoTreeEntry},
+    line::Line,
+    marks::Marks,
+    undofile,
+};
+
+use crate::editor::fileinfo::{Encoding, FileFormat, FileInfo, LineEnding};
 
+/// The document, stored as a rope so that edits and line lookups stay
+/// logarithmic in the size of the file.
+///
+/// Invariant: `text` is either empty or ends with a `\n`, i.e. every line
+/// is terminated. An empty rope is a buffer without any line. How the file
+/// differs from that on disk is tracked in `file_info.format`.
 #[derive(Default, Clone)]
 pub struct Buffer {
     pub file_info: FileInfo,
-    pub lines: Vec<Line>,
-    pub dirty: bool,
+    text: Rope,
+    history: History,
+    /// The format of the file as last loaded or saved
+    saved_format: FileFormat,
+    /// Changes with every change of the text, so that views showing the
+    /// buffer can tell when they are out of date
+    revision: u64,
+    marks: Marks,
+    checkpoints: Checkpoints,
+}
+
+/// Source of revisions, unique across all buffers
+static REVISIONS: AtomicU64 = AtomicU64::new(1);
+
+fn next_revision() -> u64 {
+    REVISIONS.fetch_add(1, Ordering::Relaxed)
 }
 
 impl Buffer {
-    pub fn insert_char(&mut self, character: char, at: Location) {
-        if at.line_index > self.lines.len() {
-            return;
+    /// Inserts `character` at `at` and returns the location right after
+    /// it, which is `at` again if it combined with the grapheme before
+    pub fn insert_char(&mut self, character: char, at: Location) -> Location {
+        if at.line_index > self.height() {
+            return at;
         }
 
-        if at.line_index == self.lines.len() {
-            self.lines.push(Line::from(&character.to_string()));
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
-            line.insert_char(character, at.grapheme_index);
-            self.dirty = true;
-        }
+        let char_index = if at.line_index == self.height() {
+            let end = self.text.len_chars();
+            self.replace(end..end, &format!("{character}\n"), at);
+            end
+        } else if let Some(char_index) = self.char_index(at) {
+            self.replace(char_index..char_index, &character.to_string(), at);
+            char_index
+        } else {
+            return at;
+        };
+        self.location_of(char_index.saturating_add(1))
     }
 
     pub fn insert_newline(&mut self, at: Location) {
-        let Location { line_index, .. } = at;
-        if line_index == self.height() {
-            self.lines.push(Line::from(""));
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(line_index) {
-            let new = line.split(at.grapheme_index);
-            self.lines.insert(at.line_index.saturating_add(1), new);
-            self.dirty = true;
+        if at.line_index == self.height() {
+            let end = self.text.len_chars();
+            self.replace(end..end, "\n", at);
+        } else if let Some(char_index) = self.char_index(at) {
+            self.replace(char_index..char_index, "\n", at);
         }
     }
 
     pub fn delete(&mut self, at: Location) {
-        if let Some(line) = self.lines.get(at.line_index) {
-            if at.grapheme_index >= line.grapheme_count()
-                && self.lines.len() > at.line_index.saturating_add(1)
-            {
-                let next_line = self.lines.remove(at.line_index.saturating_add(1));
-
-                #[allow(clippy::indexing_slicing)]
-                self.lines[at.line_index].append(next_line);
-                self.dirty = true;
-            } else if at.grapheme_index < line.grapheme_count() {
-                self.lines[at.line_index].delete(at.grapheme_index);
-                self.dirty = true;
-            }
+        if at.line_index >= self.height() {
+            return;
+        }
+
+        if let Some(range) = self.grapheme_range(at) {
+            self.replace(range, "", at);
+        } else if at.line_index.saturating_add(1) < self.height() {
+            // Join with the next line by removing the line break
+            let line_break = self
+                .text
+                .line_to_char(at.line_index.saturating_add(1))
+                .saturating_sub(1);
+            self.replace(line_break..line_break.saturating_add(1), "", at);
         }
     }
 
-    pub fn load(file_name: &str) -> Result<Self, Error> {
-        let contents = read_to_string(file_name)?;
+    /// The text from `start` up to `end`
+    pub fn text_between(&self, start: Location, end: Location) -> String {
+        let range = self.char_offset(start)..self.char_offset(end);
+        if range.is_empty() {
+            return String::new();
+        }
+        self.text.slice(range).to_string()
+    }
 
-        let mut lines = Vec::new();
+    /// Replaces the text from `start` up to `end` with `text`
+    pub fn replace_between(
+        &mut self,
+        start: Location,
+        end: Location,
+        text: &str,
+        cursor: Location,
+    ) {
+        let range = self.char_offset(start)..self.char_offset(end);
+        if !range.is_empty() || !text.is_empty() {
+            self.replace(range, text, cursor);
+        }
+    }
 
-        for value in contents.lines() {
-            lines.push(Line::from(value));
+    /// Inserts `text` at `at` and returns the location right after it
+    pub fn insert_text(&mut self, at: Location, text: &str, cursor: Location) -> Location {
+        let char_index = self.char_offset(at);
+        let end = char_index.saturating_add(text.chars().count());
+        if char_index == self.text.len_chars() && !text.ends_with('\n') {
+            // The last line keeps its line break
+            self.replace(char_index..char_index, &format!("{text}\n"), cursor);
+        } else {
+            self.replace(char_index..char_index, text, cursor);
         }
+        self.location_of(end)
+    }
+
+    /// Ends the current group of edits, making it a single undo step
+    pub fn commit(&mut self) {
+        self.history.commit();
+    }
+
+    /// Reverts the last group of edits and returns where the cursor should go
+    pub fn undo(&mut self) -> Option<Location> {
+        self.revision = next_revision();
+        let Self {
+            text,
+            marks,
+            checkpoints,
+            ..
+        } = self;
+        self.history
+            .undo(&mut |edit| Self::apply(text, marks, checkpoints, edit))
+    }
+
+    /// Reapplies the last undone group of edits and returns where the cursor should go
+    pub fn redo(&mut self) -> Option<Location> {
+        self.revision = next_revision();
+        let Self {
+            text,
+            marks,
+            checkpoints,
+            ..
+        } = self;
+        self.history
+            .redo(&mut |edit| Self::apply(text, marks, checkpoints, edit))
+    }
+
+    /// Moves `count` changes back or forth in time, across undo branches
+    pub fn step_history(&mut self, count: usize, forward: bool) -> Option<Location> {
+        self.revision = next_revision();
+        let Self {
+            text,
+            marks,
+            checkpoints,
+            ..
+        } = self;
+        self.history.step(
+            &mut |edit| Self::apply(text, marks, checkpoints, edit),
+            count,
+            forward,
+        )
+    }
+
+    /// Moves to the text state `span` earlier or later than the current one
+    pub fn travel_history(&mut self, span: Duration, forward: bool) -> Option<Location> {
+        self.revision = next_revision();
+        let Self {
+            text,
+            marks,
+            checkpoints,
+            ..
+        } = self;
+        self.history.travel(
+            &mut |edit| Self::apply(text, marks, checkpoints, edit),
+            span,
+            forward,
+        )
+    }
+
+    pub fn mark(&self, name: char) -> Option<Location> {
+        self.marks.get(name)
+    }
+
+    pub fn set_mark(&mut self, name: char, location: Location) {
+        self.marks.set(name, location);
+    }
+
+    pub fn remove_mark(&mut self, name: char) {
+        self.marks.remove(name);
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn undo_tree(&self) -> Vec<UndoTreeEntry> {
+        self.history.entries()
+    }
+
+    /// Whether the text or its file format differ from the file on disk
+    pub fn is_dirty(&self) -> bool {
+        self.history.is_modified() || self.file_info.format != self.saved_format
+    }
+
+    /// Replaces the chars in `range` with `text`, recording it in the history.
+    /// `cursor` is where the cursor is when the edit happens. The marks
+    /// follow the lines, and `.`, `[` and `]` are set to the change.
+    fn replace(&mut self, range: Range<usize>, text: &str, cursor: Location) {
+        let edit = Edit {
+            char_index: range.start,
+            removed: self.text.slice(range).to_string(),
+            inserted: text.to_string(),
+        };
+        Self::apply(&mut self.text, &mut self.marks, &self.checkpoints, &edit);
+
+        let start = self.location_of(edit.char_index);
+        let end = match edit.inserted.chars().count() {
+            0 => start,
+            inserted => {
+                self.location_of(edit.char_index.saturating_add(inserted).saturating_sub(1))
+            }
+        };
+        self.marks.set('.', start);
+        self.marks.set('[', start);
+        self.marks.set(']', end);
+        self.history.record(edit, cursor);
+        self.revision = next_revision();
+    }
+
+    /// Makes `edit` to `text`, moving the marks along with the lines. Every
+    /// change of the text goes through here, undo and redo included.
+    fn apply(text: &mut Rope, marks: &mut Marks, checkpoints: &Checkpoints, edit: &Edit) {
+        checkpoints.follow_edit(edit.char_index, |line| text.try_line_to_char(line).ok());
+        let line = text.char_to_line(edit.char_index);
+        let whole_lines = text.line_to_char(line) == edit.char_index
+            && [&edit.removed, &edit.inserted]
+                .iter()
+                .all(|text| text.is_empty() || text.ends_with('\n'));
+        let removed = edit.removed.matches('\n').count();
+        // Only a line joined to another has its marks move along its line
+        let ends = if whole_lines || removed == 0 {
+            (0, 0)
+        } else {
+            Self::edit_ends(text, line, edit)
+        };
+        marks.adjust(
+            line,
+            removed,
+            edit.inserted.matches('\n').count(),
+            whole_lines,
+            ends,
+        );
+        edit.apply(text);
+    }
+
+    /// The graphemes before the end of `edit`, which starts on `line`, on
+    /// the last line it touches, before and after it is made
+    fn edit_ends(text: &Rope, line: usize, edit: &Edit) -> (usize, usize) {
+        let count = |slice: RopeSlice| Graphemes::new(slice).count();
+        let end = edit.char_index.saturating_add(edit.removed.chars().count());
+        let old_end = count(text.slice(text.line_to_char(text.char_to_line(end))..end));
+        let new_end = match edit.inserted.rsplit_once('\n') {
+            Some((_, last)) => count(RopeSlice::from(last)),
+            None => count(text.slice(text.line_to_char(line)..edit.char_index))
+                .saturating_add(count(RopeSlice::from(edit.inserted.as_str()))),
+        };
+        (old_end, new_end)
+    }
+
+    pub fn load(file_name: &str) -> Result<Self, Error> {
+        let (contents, format) = Self::detect_format(fs::read(file_name)?);
+        let text = Rope::from_str(&contents);
+
+        let history = undofile::read(Path::new(file_name), &text).unwrap_or_default();
+
         Ok(Self {
-            lines,
+            text,
+            file_info: FileInfo {
+                format,
+                ..FileInfo::from(file_name)
+            },
+            history,
+            saved_format: format,
+            revision: next_revision(),
+            marks: Marks::default(),
+            checkpoints: Checkpoints::default(),
+        })
+    }
+
+    /// Decodes the contents of a file, normalizes them to the text invariant
+    /// and returns the format needed to write them back unchanged. Only files
+    /// which use `\r\n` consistently are treated as CRLF, in files with mixed
+    /// line endings the `\r` are kept as part of the text.
+    fn detect_format(bytes: Vec<u8>) -> (String, FileFormat) {
+        let (mut contents, encoding, bom) = encoding::decode(bytes);
+        let mut format = FileFormat {
+            encoding,
+            bom,
+            ..FileFormat::default()
+        };
+
+        let line_breaks = contents.matches('\n').count();
+        let crlf_line_breaks = contents.matches("\r\n").count();
+        if line_breaks > 0 && line_breaks == crlf_line_breaks {
+            format.line_ending = LineEnding::Crlf;
+            contents = contents.replace("\r\n", "\n");
+        }
+
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            format.missing_final_newline = true;
+            contents.push('\n');
+        }
+
+        (contents, format)
+    }
+
+    /// Decodes `bytes` with the given format. Returns `None` unless writing
+    /// the result in that format gives back exactly `bytes`.
+    fn decode_with(bytes: &[u8], format: FileFormat) -> Option<String> {
+        let data = if format.bom {
+            bytes.strip_prefix(encoding::bom(format.encoding))?
+        } else {
+            bytes
+        };
+        let mut contents = encoding::decode_as(data, format.encoding)?;
+        if format.line_ending == LineEnding::Crlf {
+            contents = contents.replace("\r\n", "\n");
+        }
+        if format.missing_final_newline && !contents.is_empty() {
+            contents.push('\n');
+        }
+
+        let mut written = Vec::new();
+        Self::write_text(&Rope::from_str(&contents), format, &mut written).ok()?;
+        (written == bytes).then_some(contents)
+    }
+
+    /// The exact bytes saving the buffer would write
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        Self::write_text(&self.text, self.file_info.format, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Replaces the content with `bytes`, as if the file had been changed on
+    /// disk, recording it in the history. The current format is kept if the
+    /// bytes can be written back unchanged with it, otherwise it is detected
+    /// again.
+    pub fn set_bytes(&mut self, bytes: Vec<u8>, cursor: Location) {
+        let (contents, format) = match Self::decode_with(&bytes, self.file_info.format) {
+            Some(contents) => (contents, self.file_info.format),
+            None => Self::detect_format(bytes),
+        };
+        self.file_info.format = format;
+        self.replace_changed(&contents, cursor);
+    }
+
+    /// Replaces the byte at `offset` of what saving would write, where
+    /// `bytes` are those bytes with the byte already changed. Only the char
+    /// the byte belongs to is decoded again, unless that doesn't give the
+    /// same bytes back on its own, like half of a UTF-8 sequence. Then all
+    /// of `bytes` are, as in `set_bytes`. Returns whether saving still
+    /// writes `bytes` because the char could be replaced on its own.
+    pub fn set_byte(&mut self, bytes: &[u8], offset: usize, cursor: Location) -> bool {
+        let Some((range, text)) = self.decode_byte(bytes, offset) else {
+            self.set_bytes(bytes.to_vec(), cursor);
+            return false;
+        };
+        if self.text.slice(range.clone()) != text.as_str() {
+            self.replace(range, &text, cursor);
+        }
+        true
+    }
+
+    /// The chars the byte at `offset` of `bytes` belongs to, and what they
+    /// decode to there. `None` if that doesn't write back to the same bytes.
+    fn decode_byte(&self, bytes: &[u8], offset: usize) -> Option<(Range<usize>, String)> {
+        let format = self.file_info.format;
+        // The first char starting after the byte
+        let (mut low, mut high) = (0, self.text.len_chars());
+        while low < high {
+            let middle = low.midpoint(high);
+            if self.byte_offset(middle) <= offset {
+                low = middle.saturating_add(1);
+            } else {
+                high = middle;
+            }
+        }
+        // Before the first char is the byte order mark
+        let start = low.checked_sub(1)?;
+        let data = bytes.get(self.byte_offset(start)..self.byte_offset(low).min(bytes.len()))?;
+
+        let mut text = encoding::decode_as(data, format.encoding)?;
+        if format.line_ending == LineEnding::Crlf {
+            text = text.replace("\r\n", "\n");
+        }
+        let format = FileFormat {
+            bom: false,
+            missing_final_newline: false,
+            ..format
+        };
+        let mut written = Vec::new();
+        Self::write_text(&Rope::from_str(&text), format, &mut written).ok()?;
+        (written == data).then_some((start..low, text))
+    }
+
+    /// Where the char at `char_index` starts in what saving would write
+    fn byte_offset(&self, char_index: usize) -> usize {
+        let format = self.file_info.format;
+        let bom = if format.bom {
+            encoding::bom(format.encoding).len()
+        } else {
+            0
+        };
+        let encoded = match format.encoding {
+            Encoding::Utf8 => self.text.char_to_byte(char_index),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                self.text.char_to_utf16_cu(char_index).saturating_mul(2)
+            }
+            Encoding::Latin1 => char_index,
+        };
+        let carriage_returns = match format.line_ending {
+            LineEnding::Lf => 0,
+            LineEnding::Crlf => self.text.char_to_line(char_index),
+        };
+        bom.saturating_add(encoded).saturating_add(carriage_returns)
+    }
+
+    /// Replaces the text with `contents` in a single edit which only covers
+    /// the part that actually differs
+    fn replace_changed(&mut self, contents: &str, cursor: Location) {
+        let old_len = self.text.len_chars();
+        let new_len = contents.chars().count();
+        let prefix = self
+            .text
+            .chars()
+            .zip(contents.chars())
+            .take_while(|(old, new)| old == new)
+            .count();
+        let suffix = self
+            .text
+            .chars_at(old_len)
+            .reversed()
+            .zip(contents.chars().rev())
+            .take(old_len.min(new_len).saturating_sub(prefix))
+            .take_while(|(old, new)| old == new)
+            .count();
+
+        let end = old_len.saturating_sub(suffix);
+        let inserted: String = contents
+            .chars()
+            .skip(prefix)
+            .take(new_len.saturating_sub(prefix).saturating_sub(suffix))
+            .collect();
+        if prefix < end || !inserted.is_empty() {
+            self.replace(prefix..end, &inserted, cursor);
+        }
+    }
+
+    /// Creates an empty buffer for a file that does not exist yet
+    pub fn new_file(file_name: &str) -> Self {
+        Self {
             file_info: FileInfo::from(file_name),
-            dirty: false,
+            revision: next_revision(),
+            ..Self::default()
+        }
+    }
+
+    /// Writes the buffer to its file and returns the number of bytes written.
+    /// With `backup`, the previous version of the file is kept as `file~`.
+    pub fn save(&mut self, backup: bool) -> Result<usize, Error> {
+        let Some(path) = &self.file_info.path else {
+            return Err(Error::new(ErrorKind::InvalidInput, "No file name"));
+        };
+        let bytes = self.write_to(path, backup)?;
+        self.history.mark_saved();
+        self.saved_format = self.file_info.format;
+        // The undo file is a convenience, failing to write it must not fail the save
+        let _ = undofile::write(path, &self.text, &self.history);
+
+        Ok(bytes)
+    }
+
+    /// Saves the buffer under a new name, which becomes its file name
+    pub fn save_as(&mut self, file_name: &str, backup: bool) -> Result<usize, Error> {
+        let file_info = FileInfo {
+            format: self.file_info.format,
+            ..FileInfo::from(file_name)
+        };
+        let previous = mem::replace(&mut self.file_info, file_info);
+        let result = self.save(backup);
+        if result.is_err() {
+            self.file_info = previous;
+        }
+        result
+    }
+
+    /// Writes a copy of the buffer without changing its file name or state,
+    /// in the format in `file_info`. Returns the number of bytes written.
+    ///
+    /// Fails without touching the file if the text cannot be represented in
+    /// the encoding of the file.
+    pub fn write_to(&self, path: &Path, backup: bool) -> Result<usize, Error> {
+        fileio::write_atomically(path, backup, |writer| {
+            Self::write_text(&self.text, self.file_info.format, writer)
         })
     }
 
-    pub fn save(&mut self) -> Result<(), Error> {
-        if let Some(path) = &self.file_info.path {
-            let mut file = File::create(path)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
+    fn write_text(text: &Rope, format: FileFormat, writer: &mut dyn Write) -> Result<usize, Error> {
+        let mut bytes = 0;
+        let mut write_bytes = |data: &[u8]| {
+            bytes = data.len().saturating_add(bytes);
+            writer.write_all(data)
+        };
+
+        if format.bom {
+            write_bytes(encoding::bom(format.encoding))?;
+        }
+
+        let mut write = |text: &str| write_bytes(&encoding::encode(text, format.encoding)?);
+
+        let mut chunks = text.chunks().peekable();
+        while let Some(mut chunk) = chunks.next() {
+            if format.missing_final_newline && chunks.peek().is_none() {
+                chunk = chunk.strip_suffix('\n').unwrap_or(chunk);
+            }
+            match format.line_ending {
+                LineEnding::Lf => write(chunk)?,
+                LineEnding::Crlf => write(&chunk.replace('\n', "\r\n"))?,
             }
-            self.dirty = false;
         }
-        Ok(())
+
+        Ok(bytes)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.text.len_chars() == 0
     }
 
     pub fn height(&self) -> usize {
-        self.lines.len()
+        self.text.len_lines().saturating_sub(1)
+    }
+
+    /// Builds the grapheme and width metadata of a single line on demand.
+    /// Only the lines that are actually looked at pay for the segmentation.
+    pub fn line(&self, line_index: usize) -> Option<Line> {
+        self.line_text(line_index).map(|text| Line::from(&text))
+    }
+
+    /// Like `line`, with only the part shown in `columns`
+    pub fn visible_line(&self, line_index: usize, columns: Range<usize>) -> Option<Line> {
+        let mut walk = self.walk(line_index, |checkpoint| checkpoint.col <= columns.start)?;
+        walk.advance_to_col(columns.start);
+        self.checkpoints.remember(&walk);
+        let col = walk.at.col;
+        Some(Line::window(walk, col, columns))
+    }
+
+    pub fn grapheme_count(&self, line_index: usize) -> usize {
+        self.walk_to(Location {
+            line_index,
+            grapheme_index: usize::MAX,
+        })
+        .map_or(0, |walk| walk.at.grapheme_index)
+    }
+
+    /// Whether the line has no text, or doesn't exist
+    pub fn is_line_empty(&self, line_index: usize) -> bool {
+        self.line_slice(line_index)
+            .is_none_or(|line| line.len_chars() == 0)
+    }
+
+    /// The screen column the grapheme at `at` starts at
+    pub fn width_until(&self, at: Location) -> usize {
+        self.walk_to(at).map_or(0, |walk| walk.at.col)
+    }
+
+    /// `at` moved back to the end of its line if it is past it
+    pub fn clamp_to_line(&self, at: Location) -> Location {
+        let end = self.walk_to(at).map_or(0, |walk| walk.at.grapheme_index);
+        Location {
+            grapheme_index: at.grapheme_index.min(end),
+            ..at
+        }
+    }
+
+    /// The graphemes of the line from `at` to its end
+    pub fn graphemes_from(&self, at: Location) -> impl Iterator<Item = Cow<'_, str>> {
+        self.walk_to(at).into_iter().flatten()
+    }
+
+    /// The graphemes of the line before `at`, from the nearest one back
+    pub fn graphemes_before(&self, at: Location) -> impl Iterator<Item = Cow<'_, str>> {
+        self.walk_to(at)
+            .zip(self.line_slice(at.line_index))
+            .map(|(walk, line)| PreviousGraphemes::new(line.slice(..walk.at.char_offset)))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Walks the graphemes of the line from the last checkpoint `usable`
+    /// for the lookup on
+    fn walk(&self, line_index: usize, usable: impl Fn(&Checkpoint) -> bool) -> Option<Walk<'_>> {
+        let line = self.line_slice(line_index)?;
+        Some(Walk::new(line, self.checkpoints.find(line_index, usable)))
+    }
+
+    /// Walks up to the grapheme at `at`, or the end of its line
+    fn walk_to(&self, at: Location) -> Option<Walk<'_>> {
+        let mut walk = self.walk(at.line_index, |checkpoint| {
+            checkpoint.grapheme_index <= at.grapheme_index
+        })?;
+        walk.advance_to_grapheme(at.grapheme_index);
+        self.checkpoints.remember(&walk);
+        Some(walk)
+    }
+
+    /// The line without its line break
+    fn line_slice(&self, line_index: usize) -> Option<RopeSlice<'_>> {
+        if line_index >= self.height() {
+            return None;
+        }
+        let start = self.text.line_to_char(line_index);
+        let end = self.text.line_to_char(line_index.saturating_add(1));
+        Some(self.text.slice(start..end.saturating_sub(1)))
+    }
+
+    /// Returns the content of the line without its line break
+    pub fn line_text(&self, line_index: usize) -> Option<Cow<'_, str>> {
+        if line_index >= self.height() {
+            return None;
+        }
+        let text: Cow<'_, str> = self.text.line(line_index).into();
+        Some(match text {
+            Cow::Borrowed(text) => Cow::Borrowed(text.strip_suffix('\n').unwrap_or(text)),
+            Cow::Owned(mut text) => {
+                if text.ends_with('\n') {
+                    text.pop();
+                }
+                Cow::Owned(text)
+            }
+        })
+    }
+
+    /// Converts a grapheme based `Location` into a char index into the rope
+    fn char_index(&self, at: Location) -> Option<usize> {
+        let offset = self.walk_to(at)?.at.char_offset;
+        Some(self.text.line_to_char(at.line_index).saturating_add(offset))
+    }
+
+    /// Like `char_index`, with the locations past the last line at the end
+    /// of the text
+    pub fn char_offset(&self, at: Location) -> usize {
+        self.char_index(at).unwrap_or_else(|| self.text.len_chars())
+    }
+
+    /// The chars from `char_index` on, which can also be walked backwards
+    pub fn chars_at(&self, char_index: usize) -> Chars<'_> {
+        self.text.chars_at(char_index.min(self.text.len_chars()))
+    }
+
+    /// Converts a char index into the rope back into a `Location`
+    pub fn location_of(&self, char_index: usize) -> Location {
+        let char_index = char_index.min(self.text.len_chars());
+        let line_index = self.text.char_to_line(char_index);
+        let offset = char_index.saturating_sub(self.text.line_to_char(line_index));
+        let grapheme_index = self
+            .walk(line_index, |checkpoint| checkpoint.char_offset <= offset)
+            .map_or(0, |mut walk| {
+                walk.advance_to_char(offset);
+                self.checkpoints.remember(&walk);
+                walk.at.grapheme_index
+            });
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    /// Returns the char range covered by the grapheme at `at`, if any
+    fn grapheme_range(&self, at: Location) -> Option<Range<usize>> {
+        let mut walk = self.walk_to(at)?;
+        let start = self
+            .text
+            .line_to_char(at.line_index)
+            .saturating_add(walk.at.char_offset);
+
+        Some(start..start.saturating_add(walk.grapheme_len()?))
     }
 }
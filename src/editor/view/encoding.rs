@@ -0,0 +1,146 @@
+use std::{
+    borrow::Cow,
+    io::{Error, ErrorKind},
+};
+
+use crate::editor::fileinfo::Encoding;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const UTF16LE_BOM: &[u8] = b"\xff\xfe";
+const UTF16BE_BOM: &[u8] = b"\xfe\xff";
+
+/// Decodes the contents of a file and returns the text, its encoding and
+/// whether it started with a byte order mark.
+///
+/// UTF-16 is only recognized by its byte order mark. Anything that is
+/// neither valid UTF-8 nor valid UTF-16 is read as Latin-1, which maps every
+/// byte to a char and back, so even binary files are saved unchanged.
+pub fn decode(bytes: Vec<u8>) -> (String, Encoding, bool) {
+    if let Some(text) = bytes.strip_prefix(UTF16LE_BOM).and_then(decode_utf16le) {
+        return (text, Encoding::Utf16Le, true);
+    }
+    if let Some(text) = bytes.strip_prefix(UTF16BE_BOM).and_then(decode_utf16be) {
+        return (text, Encoding::Utf16Be, true);
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(mut text) => {
+            let bom = text.as_bytes().starts_with(UTF8_BOM);
+            if bom {
+                text.drain(..'\u{feff}'.len_utf8());
+            }
+            (text, Encoding::Utf8, bom)
+        }
+        Err(err) => (decode_latin1(err.as_bytes()), Encoding::Latin1, false),
+    }
+}
+
+/// Decodes `bytes` as `encoding`, without a byte order mark. Returns `None`
+/// if they are not valid in that encoding.
+pub fn decode_as(bytes: &[u8], encoding: Encoding) -> Option<String> {
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+        Encoding::Utf16Le => decode_utf16le(bytes),
+        Encoding::Utf16Be => decode_utf16be(bytes),
+        Encoding::Latin1 => Some(decode_latin1(bytes)),
+    }
+}
+
+/// Encodes `text`. Fails if it contains a char the encoding cannot
+/// represent, rather than writing something else in its place.
+pub fn encode(text: &str, encoding: Encoding) -> Result<Cow<'_, [u8]>, Error> {
+    let bytes = match encoding {
+        Encoding::Utf8 => Cow::Borrowed(text.as_bytes()),
+        Encoding::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+        Encoding::Utf16Be => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        Encoding::Latin1 => text
+            .chars()
+            .map(|character| {
+                u8::try_from(character).map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("'{character}' cannot be converted to {encoding}"),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    Ok(bytes)
+}
+
+/// The byte order mark written at the start of a file, none for Latin-1
+pub fn bom(encoding: Encoding) -> &'static [u8] {
+    match encoding {
+        Encoding::Utf8 => UTF8_BOM,
+        Encoding::Utf16Le => UTF16LE_BOM,
+        Encoding::Utf16Be => UTF16BE_BOM,
+        Encoding::Latin1 => &[],
+    }
+}
+
+fn decode_utf16le(bytes: &[u8]) -> Option<String> {
+    decode_utf16(bytes, u16::from_le_bytes)
+}
+
+fn decode_utf16be(bytes: &[u8]) -> Option<String> {
+    decode_utf16(bytes, u16::from_be_bytes)
+}
+
+/// Returns `None` for an odd number of bytes or unpaired surrogates
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> Option<String> {
+    let (units, remainder) = bytes.as_chunks::<2>();
+    if !remainder.is_empty() {
+        return None;
+    }
+    char::decode_utf16(units.iter().copied().map(to_unit))
+        .collect::<Result<_, _>>()
+        .ok()
+}
+
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().copied().map(char::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "héllo wörld ✓ 𝄞\n";
+
+    #[test]
+    fn unicode_encodings_round_trip() {
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be] {
+            let mut bytes = bom(encoding).to_vec();
+            bytes.extend_from_slice(&encode(TEXT, encoding).unwrap());
+            assert_eq!(decode(bytes.clone()), (TEXT.to_string(), encoding, true));
+
+            let encoded = bytes.strip_prefix(bom(encoding)).unwrap_or_default();
+            assert_eq!(decode_as(encoded, encoding).as_deref(), Some(TEXT));
+        }
+    }
+
+    #[test]
+    fn utf8_needs_no_byte_order_mark() {
+        assert_eq!(
+            decode(TEXT.as_bytes().to_vec()),
+            (TEXT.to_string(), Encoding::Utf8, false)
+        );
+    }
+
+    #[test]
+    fn any_bytes_round_trip_as_latin1() {
+        let bytes: Vec<u8> = (0..=u8::MAX).rev().collect();
+        let (text, encoding, bom) = decode(bytes.clone());
+        assert_eq!((encoding, bom), (Encoding::Latin1, false));
+        assert_eq!(encode(&text, encoding).unwrap(), bytes.as_slice());
+    }
+
+    #[test]
+    fn unencodable_chars_are_errors() {
+        assert!(encode("✓", Encoding::Latin1).is_err());
+        assert_eq!(decode_as(&[0xff], Encoding::Utf8), None);
+        assert_eq!(decode_as(&[0x00, 0xd8], Encoding::Utf16Le), None);
+        assert_eq!(decode_as(&[0x00], Encoding::Utf16Be), None);
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, Metadata, OpenOptions},
+    io::{BufWriter, Error, ErrorKind, IntoInnerError, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Attempts at finding a free name for the temporary file
+const TEMPORARY_NAMES: usize = 100;
+
+/// Replaces the file at `path` with what `write` produces, without ever
+/// leaving a truncated file behind: the content goes to a temporary file in
+/// the same directory, which has the permissions and owner of the original,
+/// is synced to disk and is then renamed over it.
+///
+/// With `backup`, the previous version is kept as `path~`.
+/// Returns the number of bytes written.
+pub fn write_atomically<F>(path: &Path, backup: bool, write: F) -> Result<usize, Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<usize, Error>,
+{
+    // Write through symlinks instead of replacing them
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let original = fs::metadata(&target).ok();
+
+    if backup && original.is_some() {
+        fs::copy(&target, with_suffix(&target, "~"))?;
+    }
+
+    // New files get the usual permissions, as far as the umask allows
+    let mode = if original.is_some() { 0o600 } else { 0o666 };
+    replace(&target, original.as_ref(), mode, write)
+}
+
+/// Like `write_atomically`, for a file only the user may read whatever its
+/// permissions were, like an undo file holding the text of another file
+pub fn write_private<F>(path: &Path, write: F) -> Result<usize, Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<usize, Error>,
+{
+    replace(path, None, 0o600, write)
+}
+
+/// Renames a temporary file created with `mode` over `target`, after
+/// writing and syncing it
+fn replace<F>(
+    target: &Path,
+    original: Option<&Metadata>,
+    mode: u32,
+    write: F,
+) -> Result<usize, Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<usize, Error>,
+{
+    let (temporary, file) = create_temporary(target, original, mode)?;
+    let result = write_temporary(file, write)
+        .and_then(|bytes| fs::rename(&temporary, target).map(|()| bytes));
+
+    match result {
+        Ok(bytes) => {
+            sync_directory(target);
+            Ok(bytes)
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&temporary);
+            Err(err)
+        }
+    }
+}
+
+/// Creates the temporary file next to `target`. Until it has the owner and
+/// permissions of the original, only the user can read it, so that nothing
+/// written to it leaks. A name left behind by a save that crashed is
+/// skipped.
+fn create_temporary(
+    target: &Path,
+    original: Option<&Metadata>,
+    mode: u32,
+) -> Result<(PathBuf, File), Error> {
+    let mut attempt: usize = 0;
+    loop {
+        let suffix = match attempt {
+            0 => format!(".{}.tmp", process::id()),
+            _ => format!(".{}-{attempt}.tmp", process::id()),
+        };
+        let path = with_suffix(target, &suffix);
+        match open_new(&path, mode) {
+            Ok(file) => {
+                let result = original.map_or(Ok(()), |original| {
+                    preserve_owner(&file, original)
+                        .and_then(|()| file.set_permissions(original.permissions()))
+                });
+                return match result {
+                    Ok(()) => Ok((path, file)),
+                    Err(err) => {
+                        let _ = fs::remove_file(&path);
+                        Err(err)
+                    }
+                };
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists && attempt < TEMPORARY_NAMES => {
+                attempt = attempt.saturating_add(1);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_new(path: &Path, mode: u32) -> Result<File, Error> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn open_new(path: &Path, _: u32) -> Result<File, Error> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+fn write_temporary<F>(file: File, write: F) -> Result<usize, Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<usize, Error>,
+{
+    let mut writer = BufWriter::new(file);
+    let bytes = write(&mut writer)?;
+    let file = writer.into_inner().map_err(IntoInnerError::into_error)?;
+    file.sync_all()?;
+
+    Ok(bytes)
+}
+
+#[cfg(unix)]
+fn preserve_owner(file: &File, original: &Metadata) -> Result<(), Error> {
+    use std::os::unix::fs::{MetadataExt, fchown};
+
+    match fchown(file, Some(original.uid()), Some(original.gid())) {
+        // Only privileged users may give a file away, the file then belongs
+        // to whoever saved it, like any newly created file
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(not(unix))]
+fn preserve_owner(_: &File, _: &Metadata) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Makes the rename itself durable. Not all platforms allow opening a
+/// directory, so failures are ignored.
+fn sync_directory(path: &Path) {
+    if let Some(directory) = path.parent() {
+        let directory = if directory.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            directory
+        };
+        if let Ok(directory) = File::open(directory) {
+            let _ = directory.sync_all();
+        }
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{env, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    /// A directory of its own for each test, removed when dropped
+    struct Directory(PathBuf);
+
+    impl Directory {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("kedit-{name}-{}", process::id()));
+            let _ = fs::remove_dir_all(&path);
+            let _ = fs::create_dir(&path);
+            Self(path)
+        }
+
+        fn names(&self) -> Vec<String> {
+            let mut names: Vec<String> = fs::read_dir(&self.0)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        }
+    }
+
+    impl Drop for Directory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn write(text: &'static str) -> impl FnOnce(&mut dyn Write) -> Result<usize, Error> {
+        move |writer| writer.write_all(text.as_bytes()).map(|()| text.len())
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).map_or(0, |metadata| metadata.permissions().mode() & 0o777)
+    }
+
+    #[test]
+    fn saving_keeps_the_permissions_and_the_backup() {
+        let directory = Directory::new("keep");
+        let path = directory.0.join("file");
+        fs::write(&path, "old").unwrap_or_default();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap_or_default();
+
+        assert_eq!(write_atomically(&path, true, write("new")).ok(), Some(3));
+        assert_eq!(fs::read_to_string(&path).ok().as_deref(), Some("new"));
+        assert_eq!(mode(&path), 0o640);
+        assert_eq!(
+            fs::read_to_string(with_suffix(&path, "~")).ok().as_deref(),
+            Some("old")
+        );
+        assert_eq!(directory.names(), ["file", "file~"]);
+    }
+
+    #[test]
+    fn a_failed_write_leaves_the_file_as_it_was() {
+        let directory = Directory::new("fail");
+        let path = directory.0.join("file");
+        fs::write(&path, "old").unwrap_or_default();
+
+        let result = write_atomically(&path, false, |writer| {
+            writer.write_all(b"partial")?;
+            Err(Error::other("full disk"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).ok().as_deref(), Some("old"));
+        assert_eq!(directory.names(), ["file"]);
+    }
+
+    #[test]
+    fn private_files_are_for_the_user_only() {
+        let directory = Directory::new("private");
+        let path = directory.0.join("undo");
+        fs::write(&path, "old").unwrap_or_default();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap_or_default();
+
+        assert!(write_private(&path, write("new")).is_ok());
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn symlinks_are_written_through() {
+        let directory = Directory::new("link");
+        let path = directory.0.join("file");
+        let link = directory.0.join("link");
+        fs::write(&path, "old").unwrap_or_default();
+        std::os::unix::fs::symlink(&path, &link).unwrap_or_default();
+
+        assert!(write_atomically(&link, false, write("new")).is_ok());
+        assert!(fs::symlink_metadata(&link).is_ok_and(|metadata| metadata.is_symlink()));
+        assert_eq!(fs::read_to_string(&path).ok().as_deref(), Some("new"));
+    }
+}
@@ -0,0 +1,345 @@
+use std::{borrow::Cow, cell::Cell, iter::Peekable};
+
+use ropey::{RopeSlice, iter::Chunks};
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+
+use super::line::Line;
+
+/// The graphemes of a slice of the rope, segmented as they are walked so
+/// that looking at the start of a long line doesn't copy or segment the
+/// rest of it
+pub struct Graphemes<'a> {
+    text: RopeSlice<'a>,
+    chunks: Chunks<'a>,
+    chunk: &'a str,
+    /// Byte offset of `chunk` in `text`
+    chunk_start: usize,
+    cursor: GraphemeCursor,
+}
+
+impl<'a> Graphemes<'a> {
+    pub fn new(text: RopeSlice<'a>) -> Self {
+        let mut chunks = text.chunks();
+        let chunk = chunks.next().unwrap_or_default();
+        Self {
+            text,
+            chunks,
+            chunk,
+            chunk_start: 0,
+            cursor: GraphemeCursor::new(0, text.len_bytes(), true),
+        }
+    }
+}
+
+impl<'a> Iterator for Graphemes<'a> {
+    /// Borrowed unless the grapheme spans two chunks of the rope
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.cursor.cur_cursor();
+        let end = loop {
+            match self.cursor.next_boundary(self.chunk, self.chunk_start) {
+                Ok(end) => break end?,
+                Err(GraphemeIncomplete::NextChunk) => {
+                    self.chunk_start = self.chunk_start.saturating_add(self.chunk.len());
+                    self.chunk = self.chunks.next().unwrap_or_default();
+                }
+                Err(GraphemeIncomplete::PreContext(index)) => {
+                    let (chunk, chunk_start, _, _) =
+                        self.text.chunk_at_byte(index.saturating_sub(1));
+                    self.cursor.provide_context(chunk, chunk_start);
+                }
+                Err(_) => return None,
+            }
+        };
+
+        if start < self.chunk_start {
+            Some(Cow::Owned(self.text.byte_slice(start..end).to_string()))
+        } else {
+            let range =
+                start.saturating_sub(self.chunk_start)..end.saturating_sub(self.chunk_start);
+            self.chunk.get(range).map(Cow::Borrowed)
+        }
+    }
+}
+
+/// The graphemes of a slice of the rope from its end back to its start,
+/// segmented as they are walked like `Graphemes`
+pub struct PreviousGraphemes<'a> {
+    text: RopeSlice<'a>,
+    chunk: &'a str,
+    /// Byte offset of `chunk` in `text`
+    chunk_start: usize,
+    cursor: GraphemeCursor,
+}
+
+impl<'a> PreviousGraphemes<'a> {
+    pub fn new(text: RopeSlice<'a>) -> Self {
+        let length = text.len_bytes();
+        let (chunk, chunk_start, _, _) = text.chunk_at_byte(length);
+        Self {
+            text,
+            chunk,
+            chunk_start,
+            cursor: GraphemeCursor::new(length, length, true),
+        }
+    }
+}
+
+impl<'a> Iterator for PreviousGraphemes<'a> {
+    /// Borrowed unless the grapheme spans two chunks of the rope
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.cursor.cur_cursor();
+        let start = loop {
+            match self.cursor.prev_boundary(self.chunk, self.chunk_start) {
+                Ok(start) => break start?,
+                Err(GraphemeIncomplete::PrevChunk) => {
+                    let (chunk, chunk_start, _, _) =
+                        self.text.chunk_at_byte(self.chunk_start.checked_sub(1)?);
+                    self.chunk = chunk;
+                    self.chunk_start = chunk_start;
+                }
+                Err(GraphemeIncomplete::PreContext(index)) => {
+                    let (chunk, chunk_start, _, _) =
+                        self.text.chunk_at_byte(index.saturating_sub(1));
+                    self.cursor.provide_context(chunk, chunk_start);
+                }
+                Err(_) => return None,
+            }
+        };
+
+        if end > self.chunk_start.saturating_add(self.chunk.len()) {
+            Some(Cow::Owned(self.text.byte_slice(start..end).to_string()))
+        } else {
+            let range =
+                start.saturating_sub(self.chunk_start)..end.saturating_sub(self.chunk_start);
+            self.chunk.get(range).map(Cow::Borrowed)
+        }
+    }
+}
+
+/// A grapheme boundary of a line, with where it is in chars and on screen
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Checkpoint {
+    pub line_index: usize,
+    pub grapheme_index: usize,
+    /// Chars from the start of the line
+    pub char_offset: usize,
+    pub col: usize,
+}
+
+/// Walks the graphemes of a line from a checkpoint on, keeping track of
+/// where it is
+pub struct Walk<'a> {
+    pub at: Checkpoint,
+    /// The boundary before `at`, which an edit at `at` leaves valid
+    previous: Checkpoint,
+    /// The last boundary passed every `ANCHOR_SPACING` graphemes, to come
+    /// back to when going left
+    anchor: Option<Checkpoint>,
+    graphemes: Peekable<Graphemes<'a>>,
+}
+
+impl<'a> Walk<'a> {
+    /// `line` is the whole line, without its line break
+    pub fn new(line: RopeSlice<'a>, from: Checkpoint) -> Self {
+        let rest = line.slice(from.char_offset.min(line.len_chars())..);
+        Self {
+            at: from,
+            previous: from,
+            anchor: from
+                .grapheme_index
+                .is_multiple_of(ANCHOR_SPACING)
+                .then_some(from),
+            graphemes: Graphemes::new(rest).peekable(),
+        }
+    }
+
+    /// Walks up to the grapheme at `grapheme_index`, or to the end of the
+    /// line
+    pub fn advance_to_grapheme(&mut self, grapheme_index: usize) {
+        while self.at.grapheme_index < grapheme_index && self.next().is_some() {}
+    }
+
+    /// Walks over the graphemes which end at `char_offset` or before
+    pub fn advance_to_char(&mut self, char_offset: usize) {
+        while self.graphemes.peek().is_some_and(|grapheme| {
+            self.at.char_offset.saturating_add(grapheme.chars().count()) <= char_offset
+        }) {
+            self.next();
+        }
+    }
+
+    /// Walks over the graphemes which end at screen column `col` or before
+    pub fn advance_to_col(&mut self, col: usize) {
+        while self.graphemes.peek().is_some_and(|grapheme| {
+            Line::rendered_width(grapheme).saturating_add(self.at.col) <= col
+        }) {
+            self.next();
+        }
+    }
+
+    /// The length in chars of the grapheme at `at`
+    pub fn grapheme_len(&mut self) -> Option<usize> {
+        self.graphemes
+            .peek()
+            .map(|grapheme| grapheme.chars().count())
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let grapheme = self.graphemes.next()?;
+        self.previous = self.at;
+        self.at = Checkpoint {
+            grapheme_index: self.at.grapheme_index.saturating_add(1),
+            char_offset: self.at.char_offset.saturating_add(grapheme.chars().count()),
+            col: Line::rendered_width(&grapheme).saturating_add(self.at.col),
+            ..self.at
+        };
+        if self.at.grapheme_index.is_multiple_of(ANCHOR_SPACING) {
+            self.anchor = Some(self.at);
+        }
+        Some(grapheme)
+    }
+}
+
+const CHECKPOINTS: usize = 8;
+const ANCHOR_SPACING: usize = 1024;
+
+/// The places the last lookups on lines stopped at, so that the next ones
+/// near them continue from there instead of from the start of the line.
+/// Typing at the end of a very long line stays fast that way.
+#[derive(Clone, Default)]
+pub struct Checkpoints {
+    checkpoints: Cell<[Option<Checkpoint>; CHECKPOINTS]>,
+}
+
+impl Checkpoints {
+    /// The furthest checkpoint on the line that is `usable`, else the start
+    /// of the line
+    pub fn find(&self, line_index: usize, usable: impl Fn(&Checkpoint) -> bool) -> Checkpoint {
+        self.checkpoints
+            .get()
+            .into_iter()
+            .flatten()
+            .filter(|checkpoint| checkpoint.line_index == line_index && usable(checkpoint))
+            .max_by_key(|checkpoint| checkpoint.grapheme_index)
+            .unwrap_or(Checkpoint {
+                line_index,
+                ..Checkpoint::default()
+            })
+    }
+
+    /// Keeps the boundary before the one `walk` stopped at, and the last
+    /// anchor it passed, in place of the least recently used checkpoints
+    pub fn remember(&self, walk: &Walk) {
+        let mut checkpoints = self.checkpoints.get();
+        for checkpoint in walk.anchor.into_iter().chain([walk.previous]) {
+            let index = checkpoints
+                .iter()
+                .position(|kept| *kept == Some(checkpoint))
+                .unwrap_or(CHECKPOINTS.saturating_sub(1));
+            if let Some(used) = checkpoints.get_mut(..=index) {
+                used.rotate_right(1);
+            }
+            if let Some(first) = checkpoints.first_mut() {
+                *first = Some(checkpoint);
+            }
+        }
+        self.checkpoints.set(checkpoints);
+    }
+
+    /// Drops the checkpoints an edit at `char_index` changes, which are the
+    /// ones at or after it. `line_start` is where a line starts before the
+    /// edit.
+    pub fn follow_edit(&self, char_index: usize, line_start: impl Fn(usize) -> Option<usize>) {
+        let mut checkpoints = self.checkpoints.get();
+        for checkpoint in &mut checkpoints {
+            let before = checkpoint.is_some_and(|checkpoint| {
+                line_start(checkpoint.line_index)
+                    .is_some_and(|start| start.saturating_add(checkpoint.char_offset) < char_index)
+            });
+            if !before {
+                *checkpoint = None;
+            }
+        }
+        self.checkpoints.set(checkpoints);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use unicode_segmentation::UnicodeSegmentation;
+
+    use super::*;
+
+    /// Long enough to span many chunks of the rope, with graphemes of
+    /// several chars and flags, which need the context before them
+    fn text() -> String {
+        "ae\u{301}中 🇫🇷🇩🇪x\u{308}\u{301}\t".repeat(500)
+    }
+
+    #[test]
+    fn graphemes_match_str_segmentation() {
+        let text = text();
+        let rope = Rope::from_str(&text);
+        assert!(rope.chunks().count() > 1);
+
+        let graphemes: Vec<_> = Graphemes::new(rope.slice(..)).collect();
+        let expected: Vec<_> = text.graphemes(true).collect();
+        assert_eq!(graphemes, expected);
+    }
+
+    #[test]
+    fn previous_graphemes_match_reversed_str_segmentation() {
+        let text = text();
+        let rope = Rope::from_str(&text);
+
+        let graphemes: Vec<_> = PreviousGraphemes::new(rope.slice(..)).collect();
+        let expected: Vec<_> = text.graphemes(true).rev().collect();
+        assert_eq!(graphemes, expected);
+    }
+
+    #[test]
+    fn walks_from_a_checkpoint_like_from_the_start() {
+        let rope = Rope::from_str(&text());
+        let line = rope.slice(..);
+        let checkpoints = Checkpoints::default();
+
+        let mut walk = Walk::new(line, checkpoints.find(0, |_| true));
+        walk.advance_to_grapheme(3000);
+        let expected = walk.at;
+        checkpoints.remember(&walk);
+
+        let from = checkpoints.find(0, |checkpoint| checkpoint.grapheme_index <= 3000);
+        assert!(from.grapheme_index > 2000);
+        let mut walk = Walk::new(line, from);
+        walk.advance_to_grapheme(3000);
+        assert_eq!(walk.at, expected);
+
+        let mut walk = Walk::new(line, checkpoints.find(0, |_| true));
+        walk.advance_to_char(expected.char_offset);
+        assert_eq!(walk.at, expected);
+    }
+
+    #[test]
+    fn edits_drop_the_checkpoints_after_them() {
+        let rope = Rope::from_str(&text());
+        let checkpoints = Checkpoints::default();
+        let mut walk = Walk::new(rope.slice(..), checkpoints.find(0, |_| true));
+        walk.advance_to_grapheme(100);
+        checkpoints.remember(&walk);
+        let offset = walk.previous.char_offset;
+
+        checkpoints.follow_edit(offset.saturating_add(1), |_| Some(0));
+        assert_eq!(checkpoints.find(0, |_| true), walk.previous);
+        checkpoints.follow_edit(offset, |_| Some(0));
+        assert_eq!(checkpoints.find(0, |_| true).grapheme_index, 0);
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt::Write;
+
+use crate::editor::command::Direction;
+
+const BYTES_PER_ROW: usize = 16;
+/// Width of the `00000000: ` offset column
+const OFFSET_WIDTH: usize = 10;
+/// Width of the hex column: 8 pairs of bytes separated by a space
+const HEX_WIDTH: usize = 39;
+
+/// The bytes of a buffer laid out like `xxd`: an offset column, the bytes in
+/// hex and an ASCII gutter, with a cursor on a single nibble
+#[derive(Default)]
+pub struct HexView {
+    bytes: Vec<u8>,
+    /// Index of the byte under the cursor
+    offset: usize,
+    /// Whether the cursor is on the low nibble of the byte
+    low_nibble: bool,
+}
+
+impl HexView {
+    pub fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            ..Self::default()
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Replaces the bytes, e.g. after an undo, keeping the cursor in range
+    pub fn set_bytes(&mut self, bytes: Vec<u8>) {
+        self.bytes = bytes;
+        if self.offset > self.last_offset() {
+            self.offset = self.last_offset();
+            self.low_nibble = false;
+        }
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.bytes.len().div_ceil(BYTES_PER_ROW)
+    }
+
+    #[allow(clippy::integer_division)]
+    pub fn cursor_row(&self) -> usize {
+        self.offset / BYTES_PER_ROW
+    }
+
+    /// Column of the cursor within a row built by `row`
+    #[allow(clippy::integer_division, clippy::arithmetic_side_effects)]
+    pub fn cursor_col(&self) -> usize {
+        let byte_in_row = self.offset % BYTES_PER_ROW;
+        OFFSET_WIDTH + byte_in_row * 2 + byte_in_row / 2 + usize::from(self.low_nibble)
+    }
+
+    /// Renders a row as e.g. `00000010: 4865 6c6c 6f0a  Hello.`
+    pub fn row(&self, row_index: usize) -> Option<String> {
+        let start = row_index.checked_mul(BYTES_PER_ROW)?;
+        let row = self
+            .bytes
+            .get(start..self.bytes.len().min(start.saturating_add(BYTES_PER_ROW)))
+            .filter(|row| !row.is_empty())?;
+
+        let mut hex = String::with_capacity(HEX_WIDTH);
+        for (index, byte) in row.iter().enumerate() {
+            if index > 0 && index % 2 == 0 {
+                hex.push(' ');
+            }
+            let _ = write!(hex, "{byte:02x}");
+        }
+        let ascii: String = row
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    char::from(byte)
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        Some(format!("{start:08x}: {hex:<HEX_WIDTH$}  {ascii}"))
+    }
+
+    /// Moves by byte to the left and right, by row up and down.
+    /// `page_rows` is the number of rows a page up or down moves.
+    pub fn move_cursor(&mut self, direction: Direction, page_rows: usize) {
+        let page = page_rows.max(1).saturating_mul(BYTES_PER_ROW);
+        let row_start = self.cursor_row().saturating_mul(BYTES_PER_ROW);
+        match direction {
+            Direction::Left if self.low_nibble => {}
+            Direction::Left => self.offset = self.offset.saturating_sub(1),
+            Direction::Right => self.offset = self.offset.saturating_add(1),
+            Direction::Up => self.move_up(BYTES_PER_ROW),
+            Direction::Down => self.move_down(BYTES_PER_ROW),
+            Direction::PageUp => self.move_up(page),
+            Direction::PageDown => self.move_down(page),
+            Direction::Home => self.offset = row_start,
+            Direction::End => self.offset = row_start.saturating_add(BYTES_PER_ROW - 1),
+        }
+        self.offset = self.offset.min(self.last_offset());
+        self.low_nibble = false;
+    }
+
+    /// Moves one nibble back, to the previous byte from a high nibble
+    pub fn move_nibble_left(&mut self) {
+        if self.low_nibble {
+            self.low_nibble = false;
+        } else if self.offset > 0 {
+            self.offset = self.offset.saturating_sub(1);
+            self.low_nibble = true;
+        }
+    }
+
+    /// Moves to the 0-based `row`, clamped to the bytes
+    pub fn goto_row(&mut self, row: usize) {
+        self.offset = row.saturating_mul(BYTES_PER_ROW).min(self.last_offset());
+        self.low_nibble = false;
+    }
+
+    /// Replaces the nibble under the cursor with `digit` and moves to the
+    /// next nibble. Returns the offset of the changed byte, `None` if there
+    /// was no byte to change.
+    pub fn overwrite(&mut self, digit: u8) -> Option<usize> {
+        let offset = self.offset;
+        let byte = self.bytes.get_mut(offset)?;
+        *byte = if self.low_nibble {
+            (*byte & 0xf0) | (digit & 0x0f)
+        } else {
+            (*byte & 0x0f) | (digit & 0x0f).wrapping_shl(4)
+        };
+
+        if !self.low_nibble {
+            self.low_nibble = true;
+        } else if self.offset < self.last_offset() {
+            self.offset = self.offset.saturating_add(1);
+            self.low_nibble = false;
+        }
+        Some(offset)
+    }
+
+    #[allow(clippy::arithmetic_side_effects)]
+    fn move_up(&mut self, by: usize) {
+        self.offset = self
+            .offset
+            .checked_sub(by)
+            .unwrap_or(self.offset % BYTES_PER_ROW);
+    }
+
+    fn move_down(&mut self, by: usize) {
+        let offset = self.offset.saturating_add(by);
+        if offset <= self.last_offset() {
+            self.offset = offset;
+        } else if self.cursor_row().saturating_add(1) < self.row_count() {
+            // Onto the shorter last row
+            self.offset = self.last_offset();
+        }
+    }
+
+    fn last_offset(&self) -> usize {
+        self.bytes.len().saturating_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_look_like_xxd() {
+        let hex = HexView::from(b"Hello, world!\n\x00\xffmore".to_vec());
+        assert_eq!(hex.row_count(), 2);
+        assert_eq!(
+            hex.row(0).as_deref(),
+            Some("00000000: 4865 6c6c 6f2c 2077 6f72 6c64 210a 00ff  Hello, world!...")
+        );
+        assert_eq!(
+            hex.row(1).as_deref(),
+            Some("00000010: 6d6f 7265                                more")
+        );
+        assert_eq!(hex.row(2), None);
+    }
+
+    #[test]
+    fn overwriting_fills_a_byte_nibble_by_nibble() {
+        let mut hex = HexView::from(vec![0x12, 0x34]);
+        assert_eq!(hex.overwrite(0xa), Some(0));
+        assert_eq!(hex.cursor_col(), OFFSET_WIDTH + 1);
+        assert_eq!(hex.overwrite(0xb), Some(0));
+        assert_eq!(hex.overwrite(0xc), Some(1));
+        assert_eq!(hex.overwrite(0xd), Some(1));
+        // The cursor stays on the last nibble
+        assert_eq!(hex.overwrite(0xe), Some(1));
+        assert_eq!(hex.bytes(), [0xab, 0xce]);
+
+        assert_eq!(HexView::default().overwrite(1), None);
+    }
+
+    #[test]
+    fn the_cursor_stays_on_the_bytes() {
+        let mut hex = HexView::from(vec![0; 40]);
+        hex.move_cursor(Direction::End, 1);
+        assert_eq!(hex.offset, 15);
+        hex.move_cursor(Direction::PageDown, 10);
+        assert_eq!(hex.offset, 39);
+        hex.move_cursor(Direction::Up, 1);
+        assert_eq!(hex.offset, 23);
+        hex.move_cursor(Direction::PageUp, 10);
+        assert_eq!(hex.offset, 7);
+        hex.move_nibble_left();
+        assert_eq!((hex.offset, hex.low_nibble), (6, true));
+
+        hex.goto_row(2);
+        hex.set_bytes(vec![0; 20]);
+        assert_eq!(hex.offset, 19);
+    }
+}
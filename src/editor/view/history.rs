@@ -0,0 +1,448 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime},
+};
+
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use super::Location;
+
+const ROOT: usize = 0;
+
+/// A primitive change of the text: `removed` starting at `char_index` got
+/// replaced by `inserted`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Edit {
+    pub char_index: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    pub fn apply(&self, text: &mut Rope) {
+        Self::replace(text, self.char_index, &self.removed, &self.inserted);
+    }
+
+    /// The edit undoing this one
+    pub fn inverse(&self) -> Self {
+        Self {
+            char_index: self.char_index,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+
+    fn replace(text: &mut Rope, char_index: usize, from: &str, to: &str) {
+        let end = char_index.saturating_add(from.chars().count());
+        text.remove(char_index..end);
+        text.insert(char_index, to);
+    }
+
+    /// Merges `next` into `self` if it continues typing right after it
+    fn merge(&mut self, next: &Self) -> bool {
+        let insert_end = self
+            .char_index
+            .saturating_add(self.inserted.chars().count());
+
+        if next.removed.is_empty() && next.char_index == insert_end {
+            self.inserted.push_str(&next.inserted);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A state of the text, reached from its parent by applying `edits`.
+/// Nodes are numbered in creation order, so the index is the chronological
+/// sequence number of the change.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Node {
+    parent: usize,
+    children: Vec<usize>,
+    edits: Vec<Edit>,
+    /// Cursor position before the first edit of the group
+    cursor: Location,
+    time: SystemTime,
+    /// Child that `redo` follows, i.e. the branch visited last
+    redo_child: Option<usize>,
+}
+
+impl Node {
+    fn new(parent: usize, edits: Vec<Edit>, cursor: Location) -> Self {
+        Self {
+            parent,
+            children: Vec::new(),
+            edits,
+            cursor,
+            time: SystemTime::now(),
+            redo_child: None,
+        }
+    }
+}
+
+/// Edits not yet committed to the tree
+#[derive(Clone, Debug)]
+struct Pending {
+    edits: Vec<Edit>,
+    cursor: Location,
+}
+
+/// A row of the textual representation of the undo tree
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UndoTreeEntry {
+    pub graph: String,
+    pub seq: usize,
+    pub time: SystemTime,
+    pub is_current: bool,
+    pub is_saved: bool,
+}
+
+/// Undo tree of a `Buffer`.
+///
+/// Undoing and then editing starts a new branch instead of discarding the
+/// undone changes. Edits are collected in a pending group until `commit` is
+/// called, which makes a whole Insert-mode session a single undo step.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct History {
+    nodes: Vec<Node>,
+    current: usize,
+    #[serde(skip)]
+    pending: Option<Pending>,
+    saved: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::new(ROOT, Vec::new(), Location::default())],
+            current: ROOT,
+            pending: None,
+            saved: ROOT,
+        }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, edit: Edit, cursor: Location) {
+        let pending = self.pending.get_or_insert_with(|| Pending {
+            edits: Vec::new(),
+            cursor,
+        });
+
+        let merged = pending
+            .edits
+            .last_mut()
+            .is_some_and(|last| last.merge(&edit));
+        if !merged {
+            pending.edits.push(edit);
+        }
+    }
+
+    pub fn commit(&mut self) {
+        if let Some(Pending { edits, cursor }) = self.pending.take() {
+            let id = self.nodes.len();
+            self.nodes.push(Node::new(self.current, edits, cursor));
+            if let Some(parent) = self.nodes.get_mut(self.current) {
+                parent.children.push(id);
+                parent.redo_child = Some(id);
+            }
+            self.current = id;
+        }
+    }
+
+    /// Reverts the current change and returns the cursor it started at.
+    /// The edits reverting it are made with `apply`.
+    pub fn undo(&mut self, apply: &mut impl FnMut(&Edit)) -> Option<Location> {
+        self.commit();
+        if self.current == ROOT {
+            return None;
+        }
+        let node = self.nodes.get(self.current)?;
+        for edit in node.edits.iter().rev() {
+            apply(&edit.inverse());
+        }
+        let cursor = node.cursor;
+        let undone = self.current;
+        self.current = node.parent;
+        if let Some(parent) = self.nodes.get_mut(self.current) {
+            parent.redo_child = Some(undone);
+        }
+
+        Some(cursor)
+    }
+
+    /// Reapplies the most recently visited child and returns the cursor it started at
+    pub fn redo(&mut self, apply: &mut impl FnMut(&Edit)) -> Option<Location> {
+        self.commit();
+        let child = self.nodes.get(self.current)?.redo_child?;
+        let node = self.nodes.get(child)?;
+        for edit in &node.edits {
+            apply(edit);
+        }
+        self.current = child;
+
+        Some(node.cursor)
+    }
+
+    /// Moves `count` states back or forth in chronological order, across branches
+    pub fn step(
+        &mut self,
+        apply: &mut impl FnMut(&Edit),
+        count: usize,
+        forward: bool,
+    ) -> Option<Location> {
+        self.commit();
+        let last = self.nodes.len().saturating_sub(1);
+        let target = if forward {
+            self.current.saturating_add(count).min(last)
+        } else {
+            self.current.saturating_sub(count)
+        };
+
+        self.goto(apply, target)
+    }
+
+    /// Moves to the state the text was in `span` before or after the current state
+    pub fn travel(
+        &mut self,
+        apply: &mut impl FnMut(&Edit),
+        span: Duration,
+        forward: bool,
+    ) -> Option<Location> {
+        self.commit();
+        let time = self.nodes.get(self.current)?.time;
+        let target = if forward {
+            let limit = time.checked_add(span)?;
+            self.nodes
+                .iter()
+                .rposition(|node| node.time <= limit)
+                .map_or(self.current, |index| index.max(self.current))
+        } else {
+            let limit = time.checked_sub(span).unwrap_or(SystemTime::UNIX_EPOCH);
+            self.nodes
+                .iter()
+                .rposition(|node| node.time <= limit)
+                .map_or(ROOT, |index| index.min(self.current))
+        };
+
+        self.goto(apply, target)
+    }
+
+    /// Walks the tree from the current node to `target`: undo up to the common
+    /// ancestor, then redo down to the target.
+    fn goto(&mut self, apply: &mut impl FnMut(&Edit), target: usize) -> Option<Location> {
+        if target == self.current || target >= self.nodes.len() {
+            return None;
+        }
+
+        let path = self.path_from_root(target);
+        let on_path: HashSet<usize> = path.iter().copied().collect();
+        let mut cursor = None;
+        while !on_path.contains(&self.current) {
+            cursor = self.undo(apply);
+        }
+
+        let start = path.iter().position(|node| *node == self.current)?;
+        for &node in path.iter().skip(start.saturating_add(1)) {
+            if let Some(current) = self.nodes.get_mut(self.current) {
+                current.redo_child = Some(node);
+            }
+            cursor = self.redo(apply);
+        }
+
+        cursor
+    }
+
+    fn path_from_root(&self, node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+        let mut current = node;
+        while current != ROOT {
+            current = self.nodes.get(current).map_or(ROOT, |node| node.parent);
+            path.push(current);
+        }
+        path.reverse();
+        path
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.commit();
+        self.saved = self.current;
+    }
+
+    /// Checks the links between nodes of a history read from disk, so that
+    /// walking the tree always terminates at the root
+    pub fn is_consistent(&self) -> bool {
+        let len = self.nodes.len();
+        len > 0
+            && self.current < len
+            && self.saved < len
+            && self.nodes.iter().enumerate().all(|(id, node)| {
+                let descendants = id.saturating_add(1)..len;
+                (id == ROOT || node.parent < id)
+                    && node
+                        .children
+                        .iter()
+                        .all(|child| descendants.contains(child))
+                    && node
+                        .redo_child
+                        .is_none_or(|child| descendants.contains(&child))
+            })
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.pending.is_some() || self.current != self.saved
+    }
+
+    /// Lays the tree out top-down, one row per state. A node with a single
+    /// child continues in the same column; branches are drawn indented.
+    pub fn entries(&self) -> Vec<UndoTreeEntry> {
+        let mut entries = Vec::with_capacity(self.nodes.len());
+        let mut stack = vec![(ROOT, String::new(), String::new())];
+
+        while let Some((id, first_prefix, rest_prefix)) = stack.pop() {
+            let Some(node) = self.nodes.get(id) else {
+                continue;
+            };
+            entries.push(UndoTreeEntry {
+                graph: first_prefix,
+                seq: id,
+                time: node.time,
+                is_current: id == self.current,
+                is_saved: id == self.saved,
+            });
+
+            if let [child] = node.children.as_slice() {
+                stack.push((*child, rest_prefix.clone(), rest_prefix));
+                continue;
+            }
+            let last = node.children.len().saturating_sub(1);
+            for (index, child) in node.children.iter().enumerate().rev() {
+                let (first, rest) = if index == last {
+                    ("└─", "  ")
+                } else {
+                    ("├─", "│ ")
+                };
+                stack.push((
+                    *child,
+                    format!("{rest_prefix}{first}"),
+                    format!("{rest_prefix}{rest}"),
+                ));
+            }
+        }
+
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes an edit of `text` and records it
+    fn edit(
+        history: &mut History,
+        text: &mut Rope,
+        char_index: usize,
+        removed: &str,
+        inserted: &str,
+    ) {
+        let edit = Edit {
+            char_index,
+            removed: removed.to_string(),
+            inserted: inserted.to_string(),
+        };
+        edit.apply(text);
+        history.record(edit, Location::default());
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_text() {
+        let mut text = Rope::from_str("abc\n");
+        let mut history = History::default();
+        edit(&mut history, &mut text, 1, "", "X");
+        history.commit();
+        edit(&mut history, &mut text, 0, "a", "Y");
+        history.commit();
+        assert_eq!(text, "YXbc\n");
+
+        let mut apply = |edit: &Edit| edit.apply(&mut text);
+        assert!(history.undo(&mut apply).is_some());
+        assert!(history.undo(&mut apply).is_some());
+        assert!(history.undo(&mut apply).is_none());
+        assert_eq!(text, "abc\n");
+
+        let mut apply = |edit: &Edit| edit.apply(&mut text);
+        assert!(history.redo(&mut apply).is_some());
+        assert!(history.redo(&mut apply).is_some());
+        assert!(history.redo(&mut apply).is_none());
+        assert_eq!(text, "YXbc\n");
+    }
+
+    #[test]
+    fn typing_is_undone_at_once() {
+        let mut text = Rope::from_str("\n");
+        let mut history = History::default();
+        let cursor = Location {
+            line_index: 0,
+            grapheme_index: 0,
+        };
+        for (index, ch) in ["a", "b", "c"].into_iter().enumerate() {
+            let edit = Edit {
+                char_index: index,
+                removed: String::new(),
+                inserted: ch.to_string(),
+            };
+            edit.apply(&mut text);
+            history.record(edit, cursor);
+        }
+        assert_eq!(text, "abc\n");
+
+        assert_eq!(
+            history.undo(&mut |edit| edit.apply(&mut text)),
+            Some(cursor)
+        );
+        assert_eq!(text, "\n");
+    }
+
+    #[test]
+    fn undoing_then_editing_starts_a_branch() {
+        let mut text = Rope::from_str("\n");
+        let mut history = History::default();
+        edit(&mut history, &mut text, 0, "", "a");
+        history.commit();
+        history.undo(&mut |edit| edit.apply(&mut text));
+        edit(&mut history, &mut text, 0, "", "b");
+        history.commit();
+        assert_eq!(history.entries().len(), 3);
+
+        // `g-` goes back in time to the undone branch, then to the start
+        history.step(&mut |edit| edit.apply(&mut text), 1, false);
+        assert_eq!(text, "a\n");
+        history.step(&mut |edit| edit.apply(&mut text), 1, false);
+        assert_eq!(text, "\n");
+        history.step(&mut |edit| edit.apply(&mut text), 2, true);
+        assert_eq!(text, "b\n");
+        // Redo follows the branch visited last
+        history.undo(&mut |edit| edit.apply(&mut text));
+        history.redo(&mut |edit| edit.apply(&mut text));
+        assert_eq!(text, "b\n");
+    }
+
+    #[test]
+    fn saving_marks_the_current_state_unmodified() {
+        let mut text = Rope::from_str("\n");
+        let mut history = History::default();
+        assert!(!history.is_modified());
+        edit(&mut history, &mut text, 0, "", "a");
+        assert!(history.is_modified());
+        history.mark_saved();
+        assert!(!history.is_modified());
+
+        history.undo(&mut |edit| edit.apply(&mut text));
+        assert!(history.is_modified());
+        history.redo(&mut |edit| edit.apply(&mut text));
+        assert!(!history.is_modified());
+    }
+}
@@ -0,0 +1,61 @@
+use std::rc::Rc;
+
+use super::{Location, SharedBuffer};
+
+/// Jumps kept before the oldest ones are dropped
+const MAX_JUMPS: usize = 100;
+
+/// A place jumped from
+#[derive(Clone)]
+pub struct Jump {
+    pub buffer: SharedBuffer,
+    pub location: Location,
+}
+
+impl Jump {
+    fn is_same_line(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.buffer, &other.buffer)
+            && self.location.line_index == other.location.line_index
+    }
+}
+
+/// The places jumped from with commands like `G` or `'a`, to go back to
+/// with Ctrl-O and forth again with Ctrl-I
+#[derive(Clone, Default)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    /// The jump gone back to, the length of the list when at its end
+    index: usize,
+}
+
+impl JumpList {
+    /// Keeps the place of a jump at the end of the list. An older jump
+    /// from the same line is dropped.
+    pub fn push(&mut self, from: Jump) {
+        self.jumps.retain(|jump| !jump.is_same_line(&from));
+        self.jumps.push(from);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+
+    /// Goes `count` jumps back. Leaving the end of the list keeps `current`
+    /// to come back to.
+    pub fn back(&mut self, current: Jump, count: usize) -> Option<Jump> {
+        if self.index >= self.jumps.len() {
+            self.push(current);
+            self.index = self.jumps.len().saturating_sub(1);
+        }
+        self.index = self.index.checked_sub(count)?;
+        self.jumps.get(self.index).cloned()
+    }
+
+    /// Goes `count` jumps forth again, after going back
+    pub fn forward(&mut self, count: usize) -> Option<Jump> {
+        let index = self.index.saturating_add(count);
+        let jump = self.jumps.get(index)?.clone();
+        self.index = index;
+        Some(jump)
+    }
+}
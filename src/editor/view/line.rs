@@ -1,4 +1,4 @@
-use std::{fmt, ops::Range};
+use std::{borrow::Cow, fmt, ops::Range};
 
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
@@ -8,50 +8,65 @@ use super::text_fragment::{GraphemeWidth, TextFragment};
 #[derive(Clone)]
 pub struct Line {
     fragments: Vec<TextFragment>,
+    /// The column of the first fragment, when only part of the line is kept
+    start_col: usize,
 }
 
 impl Line {
     pub fn from(line_str: &str) -> Self {
-        let fragments = Self::str_to_fragments(line_str);
-        Self { fragments }
-    }
-
-    pub fn append(&mut self, other: Self) {
-        let mut updated = self.to_string();
-        updated.push_str(&other.to_string());
-        self.fragments = Self::str_to_fragments(&updated);
+        let fragments = line_str.graphemes(true).map(Self::fragment).collect();
+        Self {
+            fragments,
+            start_col: 0,
+        }
     }
 
-    pub fn split(&mut self, split_index: usize) -> Self {
+    /// Only the part of a line shown in `columns`, for rendering it, from
+    /// `graphemes` starting at column `col`. The graphemes after are not
+    /// even segmented.
+    pub fn window<'a>(
+        graphemes: impl Iterator<Item = Cow<'a, str>>,
+        mut col: usize,
+        columns: Range<usize>,
+    ) -> Self {
+        let mut start_col = col;
+        let mut fragments = Vec::new();
+        for grapheme in graphemes {
+            if col >= columns.end {
+                break;
+            }
+            let end = Self::rendered_width(&grapheme).saturating_add(col);
+            if end > columns.start {
+                if fragments.is_empty() {
+                    start_col = col;
+                }
+                fragments.push(Self::fragment(&grapheme));
+            }
+            col = end;
+        }
         Self {
-            fragments: self.fragments.split_off(split_index),
+            fragments,
+            start_col,
         }
     }
 
-    fn str_to_fragments(line_str: &str) -> Vec<TextFragment> {
-        line_str
-            .graphemes(true)
-            .map(|grapheme| {
-                let (replacement, rendered_width) = Self::replacement_character(grapheme)
-                    .map_or_else(
-                        || {
-                            let unicode_width = grapheme.width();
-                            let rendered_width = match unicode_width {
-                                0 | 1 => GraphemeWidth::Half,
-                                _ => GraphemeWidth::Full,
-                            };
-                            (None, rendered_width)
-                        },
-                        |replacement| (Some(replacement), GraphemeWidth::Half),
-                    );
+    fn fragment(grapheme: &str) -> TextFragment {
+        TextFragment {
+            grapheme: grapheme.to_string(),
+            rendered_width: Self::rendered_width(grapheme),
+            replacement: Self::replacement_character(grapheme),
+        }
+    }
 
-                TextFragment {
-                    grapheme: grapheme.to_string(),
-                    rendered_width,
-                    replacement,
-                }
-            })
-            .collect()
+    /// How wide the grapheme is on screen, as shown by the editor
+    pub fn rendered_width(grapheme: &str) -> GraphemeWidth {
+        if Self::replacement_character(grapheme).is_some() {
+            return GraphemeWidth::Half;
+        }
+        match grapheme.width() {
+            0 | 1 => GraphemeWidth::Half,
+            _ => GraphemeWidth::Full,
+        }
     }
 
     fn replacement_character(for_str: &str) -> Option<char> {
@@ -62,10 +77,11 @@ impl Line {
             _ if width > 0 && for_str.trim().is_empty() => Some('␣'),
             _ if width == 0 => {
                 let mut chars = for_str.chars();
-                if let Some(ch) = chars.next() {
-                    if ch.is_control() && chars.next().is_none() {
-                        return Some('▯');
-                    }
+                if let Some(ch) = chars.next()
+                    && ch.is_control()
+                    && chars.next().is_none()
+                {
+                    return Some('▯');
                 }
                 Some('.')
             }
@@ -79,7 +95,7 @@ impl Line {
         }
 
         let mut result = String::new();
-        let mut current_pos = 0;
+        let mut current_pos = self.start_col;
         for fragment in &self.fragments {
             let fragment_end = fragment.rendered_width.saturating_add(current_pos);
             if current_pos >= range.end {
@@ -99,7 +115,20 @@ impl Line {
         result
     }
 
-    pub fn grapheme_count(&self) -> usize {
+    pub fn width(&self) -> usize {
+        self.width_until(self.fragments.len())
+    }
+
+    /// The index of the grapheme shown at screen column `col`, or the number
+    /// of graphemes when the line is shorter
+    pub fn grapheme_index_at(&self, col: usize) -> usize {
+        let mut width: usize = 0;
+        for (index, fragment) in self.fragments.iter().enumerate() {
+            width = fragment.rendered_width.saturating_add(width);
+            if width > col {
+                return index;
+            }
+        }
         self.fragments.len()
     }
 
@@ -113,32 +142,6 @@ impl Line {
             })
             .sum()
     }
-
-    pub fn insert_char(&mut self, character: char, grapheme_index: usize) {
-        let mut result = String::new();
-
-        for (index, fragment) in self.fragments.iter().enumerate() {
-            if index == grapheme_index {
-                result.push(character);
-            }
-            result.push_str(&fragment.grapheme);
-        }
-        if grapheme_index >= self.fragments.len() {
-            result.push(character);
-        }
-        self.fragments = Self::str_to_fragments(&result);
-    }
-
-    pub fn delete(&mut self, grapheme_index: usize) {
-        let mut result = String::new();
-        for (index, fragment) in self.fragments.iter().enumerate() {
-            if index == grapheme_index {
-                continue;
-            }
-            result.push_str(&fragment.grapheme);
-        }
-        self.fragments = Self::str_to_fragments(&result);
-    }
 }
 
 impl fmt::Display for Line {
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+
+use super::Location;
+
+/// The places marked in a buffer: `a` to `z` set with `m`, `A` to `Z`
+/// which only one buffer has at a time, and the ones the editor sets,
+/// like `.` for the last change. Marks stay on their line as lines are
+/// inserted or deleted above them.
+#[derive(Clone, Default)]
+pub struct Marks {
+    marks: BTreeMap<char, Location>,
+}
+
+impl Marks {
+    pub fn get(&self, name: char) -> Option<Location> {
+        self.marks.get(&name).copied()
+    }
+
+    pub fn set(&mut self, name: char, location: Location) {
+        self.marks.insert(name, location);
+    }
+
+    pub fn remove(&mut self, name: char) {
+        self.marks.remove(&name);
+    }
+
+    /// Follows an edit starting on `line`, which replaced `removed` line
+    /// breaks with `inserted` ones. For an edit of whole lines, the marks
+    /// on lines that are gone are deleted. Otherwise the lines joined
+    /// into `line` take their marks along. `ends` are the graphemes before
+    /// the end of the edit on its last line, before and after it, which
+    /// the marks after the end keep their distance to.
+    pub fn adjust(
+        &mut self,
+        line: usize,
+        removed: usize,
+        inserted: usize,
+        whole_lines: bool,
+        ends: (usize, usize),
+    ) {
+        if removed == 0 && inserted == 0 {
+            return;
+        }
+        let after = line.saturating_add(removed);
+        self.marks.retain(|_, location| {
+            let offset = location.line_index.wrapping_sub(line);
+            if location.line_index < line || (!whole_lines && location.line_index == line) {
+                return true;
+            }
+            if location.line_index > after || (whole_lines && location.line_index == after) {
+                location.line_index = location
+                    .line_index
+                    .saturating_add(inserted)
+                    .saturating_sub(removed);
+                return true;
+            }
+            // On a line the edit replaced
+            if offset < inserted || (!whole_lines && offset == inserted) {
+                return true;
+            }
+            if whole_lines {
+                return false;
+            }
+            // Marks in the removed text go to the end of the edit
+            let (old_end, new_end) = ends;
+            location.line_index = line.saturating_add(inserted);
+            location.grapheme_index = if offset == removed && location.grapheme_index >= old_end {
+                location
+                    .grapheme_index
+                    .saturating_sub(old_end)
+                    .saturating_add(new_end)
+            } else {
+                new_end
+            };
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marks(lines: &[(char, usize)]) -> Marks {
+        let mut marks = Marks::default();
+        for &(name, line_index) in lines {
+            marks.set(
+                name,
+                Location {
+                    line_index,
+                    grapheme_index: 1,
+                },
+            );
+        }
+        marks
+    }
+
+    fn line(marks: &Marks, name: char) -> Option<usize> {
+        marks.get(name).map(|location| location.line_index)
+    }
+
+    #[test]
+    fn inserted_lines_push_the_marks_below_down() {
+        let mut marks = marks(&[('a', 1), ('b', 2), ('c', 3)]);
+        // Two lines put above line 2
+        marks.adjust(2, 0, 2, true, (0, 0));
+        assert_eq!(line(&marks, 'a'), Some(1));
+        assert_eq!(line(&marks, 'b'), Some(4));
+        assert_eq!(line(&marks, 'c'), Some(5));
+    }
+
+    #[test]
+    fn deleted_lines_take_their_marks_along() {
+        let mut marks = marks(&[('a', 1), ('b', 2), ('c', 3), ('d', 4)]);
+        // `2dd` on line 2
+        marks.adjust(2, 2, 0, true, (0, 0));
+        assert_eq!(line(&marks, 'a'), Some(1));
+        assert_eq!(line(&marks, 'b'), None);
+        assert_eq!(line(&marks, 'c'), None);
+        assert_eq!(line(&marks, 'd'), Some(2));
+    }
+
+    #[test]
+    fn joined_lines_keep_their_marks() {
+        let mut marks = marks(&[('a', 1), ('b', 2), ('c', 3)]);
+        marks.set(
+            'd',
+            Location {
+                line_index: 2,
+                grapheme_index: 4,
+            },
+        );
+        // `J` on line 1, which is five graphemes long, and line 2 indented
+        // by two blanks: the line break and the indentation become a blank
+        marks.adjust(1, 1, 0, false, (2, 6));
+        assert_eq!(line(&marks, 'a'), Some(1));
+        assert_eq!(line(&marks, 'c'), Some(2));
+        // On the indentation, which is gone
+        assert_eq!(
+            marks.get('b'),
+            Some(Location {
+                line_index: 1,
+                grapheme_index: 6,
+            })
+        );
+        assert_eq!(
+            marks.get('d'),
+            Some(Location {
+                line_index: 1,
+                grapheme_index: 8,
+            })
+        );
+    }
+
+    #[test]
+    fn splitting_a_line_keeps_the_marks_on_it() {
+        let mut marks = marks(&[('a', 1), ('b', 2)]);
+        // Enter in the middle of line 1
+        marks.adjust(1, 0, 1, false, (0, 0));
+        assert_eq!(line(&marks, 'a'), Some(1));
+        assert_eq!(line(&marks, 'b'), Some(3));
+    }
+
+    #[test]
+    fn edits_within_a_line_move_nothing() {
+        let mut marks = marks(&[('a', 1)]);
+        marks.adjust(1, 0, 0, false, (0, 0));
+        assert_eq!(line(&marks, 'a'), Some(1));
+    }
+}
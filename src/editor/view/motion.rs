@@ -0,0 +1,439 @@
+use std::ops::Range;
+
+use super::{Buffer, Location};
+use crate::editor::command::{Motion, WordKind};
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Class {
+    Blank,
+    Punctuation,
+    Word,
+}
+
+impl Class {
+    fn of(grapheme: &str) -> Self {
+        match grapheme.chars().next() {
+            None => Self::Blank,
+            Some(ch) if ch.is_whitespace() => Self::Blank,
+            Some(ch) if ch.is_alphanumeric() || ch == '_' => Self::Word,
+            Some(_) => Self::Punctuation,
+        }
+    }
+}
+
+/// Where `motion` takes the cursor from `from`, `count` times over.
+/// `None` if the motion fails because it can't move at all, like `j` on
+/// the last line.
+pub fn target(
+    buffer: &Buffer,
+    from: Location,
+    motion: Motion,
+    count: Option<usize>,
+) -> Option<Location> {
+    let line_index = from.line_index;
+    let times = count.unwrap_or(1);
+    let target = match motion {
+        Motion::LineStart => location(line_index, 0),
+        Motion::Left | Motion::Right | Motion::Up | Motion::Down
+            if line_index >= buffer.height() =>
+        {
+            return None;
+        }
+        Motion::FirstNonBlank => first_non_blank(buffer, line_index),
+        // `3$` goes to the end of the line two lines down
+        Motion::LineEnd if times > 1 => end_of_line(
+            buffer,
+            line_index
+                .saturating_add(times.saturating_sub(1))
+                .min(last_line(buffer)),
+        ),
+        Motion::LineEnd => end_of_line(buffer, line_index),
+        // With a count, `gg` and `G` go to that line
+        Motion::FirstLine | Motion::LastLine if count.is_some() => {
+            first_non_blank(buffer, times.saturating_sub(1).min(last_line(buffer)))
+        }
+        Motion::FirstLine => first_non_blank(buffer, 0),
+        Motion::LastLine => first_non_blank(buffer, last_line(buffer)),
+        Motion::Mark { name, exact } => {
+            // `''` and ``` `` ``` both go back to before the last jump
+            let name = if name == '`' { '\'' } else { name };
+            let mark = buffer.mark(name)?;
+            let line_index = mark.line_index.min(last_line(buffer));
+            if exact {
+                buffer.clamp_to_line(location(line_index, mark.grapheme_index))
+            } else {
+                first_non_blank(buffer, line_index)
+            }
+        }
+        Motion::Left => location(line_index, from.grapheme_index.saturating_sub(times)),
+        Motion::Right => buffer.clamp_to_line(location(
+            line_index,
+            from.grapheme_index.saturating_add(times),
+        )),
+        Motion::Up => to_line(buffer, from, line_index.saturating_sub(times)),
+        Motion::Down => to_line(
+            buffer,
+            from,
+            line_index.saturating_add(times).min(last_line(buffer)),
+        ),
+        _ => {
+            let mut current = from;
+            for _ in 0..times {
+                let next = step(buffer, current, motion);
+                if next == current {
+                    break;
+                }
+                current = next;
+            }
+            current
+        }
+    };
+    let absolute = matches!(
+        motion,
+        Motion::LineStart
+            | Motion::FirstNonBlank
+            | Motion::LineEnd
+            | Motion::FirstLine
+            | Motion::LastLine
+            | Motion::Mark { .. }
+    );
+    (absolute || target != from).then_some(target)
+}
+
+/// `cw` changes up to the end of the word like `ce`, but stops at the end
+/// of the word under the cursor even when the cursor is already there.
+/// `None` if the cursor is on a blank, where `cw` is just `dw`.
+pub fn change_word_end(
+    buffer: &Buffer,
+    from: Location,
+    kind: WordKind,
+    count: Option<usize>,
+) -> Option<Location> {
+    let mut classes = buffer
+        .graphemes_from(from)
+        .map(|grapheme| Class::of(&grapheme));
+    let mut class = classes.next().filter(|&class| class != Class::Blank)?;
+    let mut end = from.grapheme_index;
+    for next in classes {
+        if ends_word(kind, class, next) {
+            break;
+        }
+        end = end.saturating_add(1);
+        class = next;
+    }
+    let mut current = location(from.line_index, end);
+    for _ in 1..count.unwrap_or(1) {
+        current = word_end(buffer, current, kind);
+    }
+    Some(current)
+}
+
+/// A single word or paragraph motion
+fn step(buffer: &Buffer, from: Location, motion: Motion) -> Location {
+    match motion {
+        Motion::WordStart(kind) => word_start(buffer, from, kind),
+        Motion::PreviousWordStart(kind) => previous_word_start(buffer, from, kind),
+        Motion::WordEnd(kind) => word_end(buffer, from, kind),
+        Motion::PreviousWordEnd(kind) => previous_word_end(buffer, from, kind),
+        Motion::NextParagraph => next_paragraph(buffer, from.line_index),
+        Motion::PreviousParagraph => previous_paragraph(buffer, from.line_index),
+        _ => from,
+    }
+}
+
+/// Whether a word starts at a grapheme of `class` after one of `previous`.
+/// For `WordKind::Word` letters and punctuation are words of their own, so
+/// that `foo.bar` is three words like in vim. The start of a line counts as
+/// a blank.
+fn starts_word(kind: WordKind, previous: Class, class: Class) -> bool {
+    class != Class::Blank
+        && match kind {
+            WordKind::Word => class != previous,
+            WordKind::BigWord => previous == Class::Blank,
+        }
+}
+
+/// Whether a word ends at a grapheme of `class` before one of `next`
+fn ends_word(kind: WordKind, class: Class, next: Class) -> bool {
+    class != Class::Blank && (next == Class::Blank || starts_word(kind, class, next))
+}
+
+/// The words of a line as grapheme ranges. An empty line counts as a single
+/// empty word.
+pub fn words(buffer: &Buffer, line_index: usize, kind: WordKind) -> Vec<Range<usize>> {
+    if line_index >= buffer.height() {
+        return Vec::new();
+    }
+    if buffer.is_line_empty(line_index) {
+        return vec![Range { start: 0, end: 0 }];
+    }
+
+    let mut words: Vec<Range<usize>> = Vec::new();
+    let mut previous = Class::Blank;
+    for (index, grapheme) in buffer.graphemes_from(location(line_index, 0)).enumerate() {
+        let class = Class::of(&grapheme);
+        let next = index.saturating_add(1);
+        if starts_word(kind, previous, class) {
+            words.push(index..next);
+        } else if let Some(word) = words.last_mut().filter(|_| class != Class::Blank) {
+            word.end = next;
+        }
+        previous = class;
+    }
+    words
+}
+
+fn word_start(buffer: &Buffer, from: Location, kind: WordKind) -> Location {
+    let mut classes = buffer
+        .graphemes_from(from)
+        .map(|grapheme| Class::of(&grapheme));
+    if let Some(mut previous) = classes.next() {
+        for (grapheme_index, class) in (from.grapheme_index.saturating_add(1)..).zip(classes) {
+            if starts_word(kind, previous, class) {
+                return location(from.line_index, grapheme_index);
+            }
+            previous = class;
+        }
+    }
+
+    for line_index in from.line_index.saturating_add(1)..buffer.height() {
+        if buffer.is_line_empty(line_index) {
+            return location(line_index, 0);
+        }
+        if let Some(grapheme_index) = buffer
+            .graphemes_from(location(line_index, 0))
+            .position(|grapheme| Class::of(&grapheme) != Class::Blank)
+        {
+            return location(line_index, grapheme_index);
+        }
+    }
+    end_of_line(buffer, last_line(buffer))
+}
+
+/// Empty lines before the one of `from` are words of their own
+fn previous_word_start(buffer: &Buffer, from: Location, kind: WordKind) -> Location {
+    for line_index in (0..=from.line_index).rev() {
+        let before = if line_index == from.line_index {
+            from.grapheme_index
+        } else {
+            usize::MAX
+        };
+        if before > 0 && line_index < buffer.height() && buffer.is_line_empty(line_index) {
+            return location(line_index, 0);
+        }
+
+        let end = buffer.clamp_to_line(location(line_index, before));
+        let mut classes = buffer
+            .graphemes_before(end)
+            .map(|grapheme| Class::of(&grapheme))
+            .peekable();
+        let mut grapheme_index = end.grapheme_index;
+        while let Some(class) = classes.next() {
+            grapheme_index = grapheme_index.saturating_sub(1);
+            let previous = classes.peek().copied().unwrap_or(Class::Blank);
+            if starts_word(kind, previous, class) {
+                return location(line_index, grapheme_index);
+            }
+        }
+    }
+    Location::default()
+}
+
+/// Empty lines are skipped, there is no end to stop at
+fn word_end(buffer: &Buffer, from: Location, kind: WordKind) -> Location {
+    for line_index in from.line_index..buffer.height() {
+        let start = if line_index == from.line_index {
+            from.grapheme_index.saturating_add(1)
+        } else {
+            0
+        };
+        let mut classes = buffer
+            .graphemes_from(location(line_index, start))
+            .map(|grapheme| Class::of(&grapheme))
+            .peekable();
+        let mut grapheme_index = start;
+        while let Some(class) = classes.next() {
+            let next = classes.peek().copied().unwrap_or(Class::Blank);
+            if ends_word(kind, class, next) {
+                return location(line_index, grapheme_index);
+            }
+            grapheme_index = grapheme_index.saturating_add(1);
+        }
+    }
+    end_of_line(buffer, last_line(buffer))
+}
+
+/// Empty lines before the one of `from` are words of their own
+fn previous_word_end(buffer: &Buffer, from: Location, kind: WordKind) -> Location {
+    for line_index in (0..=from.line_index).rev() {
+        let before = if line_index == from.line_index {
+            from.grapheme_index
+        } else {
+            usize::MAX
+        };
+        if before > 0 && line_index < buffer.height() && buffer.is_line_empty(line_index) {
+            return location(line_index, 0);
+        }
+
+        let end = buffer.clamp_to_line(location(line_index, before));
+        let mut next = buffer
+            .graphemes_from(end)
+            .next()
+            .map_or(Class::Blank, |grapheme| Class::of(&grapheme));
+        let mut grapheme_index = end.grapheme_index;
+        for grapheme in buffer.graphemes_before(end) {
+            grapheme_index = grapheme_index.saturating_sub(1);
+            let class = Class::of(&grapheme);
+            if ends_word(kind, class, next) {
+                return location(line_index, grapheme_index);
+            }
+            next = class;
+        }
+    }
+    Location::default()
+}
+
+/// The next empty line after the paragraph, or the end of the last line
+fn next_paragraph(buffer: &Buffer, from: usize) -> Location {
+    let last = last_line(buffer);
+    let is_empty = |line_index| buffer.is_line_empty(line_index);
+    let mut line_index = from;
+    while line_index < last && is_empty(line_index) {
+        line_index = line_index.saturating_add(1);
+    }
+    while line_index < last && !is_empty(line_index) {
+        line_index = line_index.saturating_add(1);
+    }
+    if is_empty(line_index) {
+        location(line_index, 0)
+    } else {
+        end_of_line(buffer, line_index)
+    }
+}
+
+/// The empty line before the paragraph, or the start of the first line
+fn previous_paragraph(buffer: &Buffer, from: usize) -> Location {
+    let is_empty = |line_index| buffer.is_line_empty(line_index);
+    let mut line_index = from.min(last_line(buffer));
+    while line_index > 0 && is_empty(line_index) {
+        line_index = line_index.saturating_sub(1);
+    }
+    while line_index > 0 && !is_empty(line_index) {
+        line_index = line_index.saturating_sub(1);
+    }
+    location(line_index, 0)
+}
+
+/// Keeps the column where the line is long enough
+fn to_line(buffer: &Buffer, from: Location, line_index: usize) -> Location {
+    buffer.clamp_to_line(location(line_index, from.grapheme_index))
+}
+
+pub fn first_non_blank(buffer: &Buffer, line_index: usize) -> Location {
+    let grapheme_index = buffer
+        .graphemes_from(location(line_index, 0))
+        .take_while(|grapheme| Class::of(grapheme) == Class::Blank)
+        .count();
+    location(line_index, grapheme_index)
+}
+
+pub fn end_of_line(buffer: &Buffer, line_index: usize) -> Location {
+    location(line_index, buffer.grapheme_count(line_index))
+}
+
+fn last_line(buffer: &Buffer) -> usize {
+    buffer.height().saturating_sub(1)
+}
+
+const fn location(line_index: usize, grapheme_index: usize) -> Location {
+    Location {
+        grapheme_index,
+        line_index,
+    }
+}
+
+pub const fn is_before(first: Location, second: Location) -> bool {
+    first.line_index < second.line_index
+        || (first.line_index == second.line_index && first.grapheme_index < second.grapheme_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(Location::default(), text, Location::default());
+        buffer
+    }
+
+    #[test]
+    fn words_split_where_letters_meet_punctuation() {
+        let buffer = buffer("  foo.bar(baz) qux_1\n");
+        assert_eq!(
+            words(&buffer, 0, WordKind::Word),
+            [2..5, 5..6, 6..9, 9..10, 10..13, 13..14, 15..20]
+        );
+    }
+
+    #[test]
+    fn big_words_split_at_blanks_only() {
+        let buffer = buffer("  foo.bar(baz)\tqux_1 \n");
+        assert_eq!(words(&buffer, 0, WordKind::BigWord), [2..14, 15..20]);
+    }
+
+    #[test]
+    fn words_count_graphemes() {
+        let buffer = buffer("e\u{301}té 中文 🇫🇷\n");
+        assert_eq!(words(&buffer, 0, WordKind::Word), [0..3, 4..6, 7..8]);
+    }
+
+    #[test]
+    fn word_motions_walk_over_lines() {
+        let buffer = buffer("foo.bar\n\n  中文 x\n");
+        let step = |from, motion| step(&buffer, from, motion);
+        let word = WordKind::Word;
+
+        assert_eq!(
+            step(location(0, 0), Motion::WordStart(word)),
+            location(0, 3)
+        );
+        assert_eq!(
+            step(location(0, 4), Motion::WordStart(word)),
+            location(1, 0)
+        );
+        assert_eq!(
+            step(location(1, 0), Motion::WordStart(word)),
+            location(2, 2)
+        );
+        assert_eq!(step(location(2, 2), Motion::WordEnd(word)), location(2, 3));
+        assert_eq!(step(location(0, 6), Motion::WordEnd(word)), location(2, 3));
+        assert_eq!(
+            step(location(2, 2), Motion::PreviousWordStart(word)),
+            location(1, 0)
+        );
+        assert_eq!(
+            step(location(1, 0), Motion::PreviousWordStart(word)),
+            location(0, 4)
+        );
+        assert_eq!(
+            step(location(2, 5), Motion::PreviousWordEnd(word)),
+            location(2, 3)
+        );
+        assert_eq!(
+            change_word_end(&buffer, location(0, 1), word, None),
+            Some(location(0, 2))
+        );
+        assert_eq!(first_non_blank(&buffer, 2), location(2, 2));
+    }
+
+    #[test]
+    fn an_empty_line_is_an_empty_word() {
+        let buffer = buffer("\nfoo\n");
+        assert_eq!(
+            words(&buffer, 0, WordKind::Word),
+            [Range { start: 0, end: 0 }]
+        );
+        assert_eq!(words(&buffer, 2, WordKind::Word), []);
+    }
+}
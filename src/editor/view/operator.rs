@@ -0,0 +1,502 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{
+    Buffer, Location,
+    motion::{self, change_word_end, end_of_line, first_non_blank, is_before},
+    textobject,
+};
+use crate::editor::{
+    command::{Motion, Operator, Target},
+    register::{Register, RegisterKind},
+};
+
+/// The text an operator acts on, from `start` up to `end`. A linewise span
+/// starts at the start of its first line and ends at the start of the line
+/// after its last one.
+#[derive(Clone, Copy)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+    pub linewise: bool,
+}
+
+impl Span {
+    pub const fn lines(first: usize, last: usize) -> Self {
+        Self {
+            start: Location {
+                line_index: first,
+                grapheme_index: 0,
+            },
+            end: Location {
+                line_index: last.saturating_add(1),
+                grapheme_index: 0,
+            },
+            linewise: true,
+        }
+    }
+
+    /// The last line the span touches
+    pub const fn last_line(&self) -> usize {
+        if self.linewise {
+            self.end.line_index.saturating_sub(1)
+        } else {
+            self.end.line_index
+        }
+    }
+}
+
+/// What `operator` acts on when applied from `from`. `None` if the motion fails.
+pub fn span(
+    buffer: &Buffer,
+    from: Location,
+    operator: Operator,
+    target: Target,
+    count: Option<usize>,
+) -> Option<Span> {
+    let motion = match target {
+        Target::Motion(motion) => motion,
+        Target::Object { object, around } => {
+            return textobject::span(buffer, from, object, around, count);
+        }
+        Target::Lines => {
+            let last_line = buffer.height().checked_sub(1)?;
+            if from.line_index > last_line {
+                return None;
+            }
+            let last = from
+                .line_index
+                .saturating_add(count.unwrap_or(1).saturating_sub(1))
+                .min(last_line);
+            return Some(Span::lines(from.line_index, last));
+        }
+    };
+
+    if operator == Operator::Change
+        && let Motion::WordStart(kind) = motion
+        && let Some(end) = change_word_end(buffer, from, kind, count)
+    {
+        return Some(Span {
+            start: from,
+            end: after(buffer, end),
+            linewise: false,
+        });
+    }
+
+    let to = motion::target(buffer, from, motion, count)?;
+    let (start, mut end) = if is_before(to, from) {
+        (to, from)
+    } else {
+        (from, to)
+    };
+    if motion.is_linewise() {
+        return Some(Span::lines(start.line_index, end.line_index));
+    }
+    if motion.is_inclusive() {
+        return Some(Span {
+            start,
+            end: after(buffer, end),
+            linewise: false,
+        });
+    }
+
+    // `dw` on the last word of a line stops at the end of that line
+    if matches!(motion, Motion::WordStart(_))
+        && end.line_index > start.line_index
+        && end.grapheme_index <= first_non_blank(buffer, end.line_index).grapheme_index
+    {
+        end = end_of_line(buffer, end.line_index.saturating_sub(1));
+    }
+    // An exclusive motion to the start of a later line ends with the line
+    // before it, and takes whole lines if it started before the text
+    if end.grapheme_index == 0 && end.line_index > start.line_index {
+        let last = end.line_index.saturating_sub(1);
+        if start.grapheme_index <= first_non_blank(buffer, start.line_index).grapheme_index {
+            return Some(Span::lines(start.line_index, last));
+        }
+        end = end_of_line(buffer, last);
+    }
+
+    Some(Span {
+        start,
+        end,
+        linewise: false,
+    })
+}
+
+/// Applies `operator` to `span`, keeping the text deleted or yanked in
+/// `register`. `>` and `<` shift by `shift_width` columns. Returns where
+/// the cursor goes.
+pub fn apply(
+    buffer: &mut Buffer,
+    span: Span,
+    operator: Operator,
+    cursor: Location,
+    register: &mut Register,
+    shift_width: usize,
+) -> Location {
+    let Span {
+        start,
+        end,
+        linewise,
+    } = span;
+    let text = buffer.text_between(start, end);
+    let kind = if linewise {
+        RegisterKind::Linewise
+    } else {
+        RegisterKind::Charwise
+    };
+
+    match operator {
+        Operator::Yank => {
+            *register = Register { text, kind };
+            if linewise && start.line_index == cursor.line_index {
+                cursor
+            } else {
+                start
+            }
+        }
+        Operator::Delete => {
+            *register = Register { text, kind };
+            buffer.replace_between(start, end, "", cursor);
+            buffer.commit();
+            if linewise {
+                let line_index = start.line_index.min(buffer.height().saturating_sub(1));
+                first_non_blank(buffer, line_index)
+            } else {
+                start
+            }
+        }
+        Operator::Change => {
+            *register = Register { text, kind };
+            // Changed lines are emptied but not removed, and the change
+            // goes on in Insert mode, so it isn't committed yet
+            let end = if linewise {
+                end_of_line(buffer, span.last_line())
+            } else {
+                end
+            };
+            buffer.replace_between(start, end, "", cursor);
+            start
+        }
+        Operator::Indent | Operator::Outdent => {
+            let first = Location {
+                line_index: start.line_index,
+                grapheme_index: 0,
+            };
+            let last = end_of_line(buffer, span.last_line());
+            let lines = buffer.text_between(first, last);
+            let shifted = lines
+                .split('\n')
+                .map(|line| shift(line, operator == Operator::Indent, shift_width))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if shifted != lines {
+                buffer.replace_between(first, last, &shifted, cursor);
+                buffer.commit();
+            }
+            first_non_blank(buffer, start.line_index)
+        }
+        Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+            let changed = change_case(&text, operator);
+            if changed != text {
+                buffer.replace_between(start, end, &changed, cursor);
+                buffer.commit();
+            }
+            if linewise && start.line_index == cursor.line_index {
+                cursor
+            } else {
+                start
+            }
+        }
+    }
+}
+
+/// Applies `operator` to each line of a block, keeping the text deleted or
+/// yanked in `register` as a block. Returns where the cursor goes, the top
+/// left corner of the block.
+pub fn apply_block(
+    buffer: &mut Buffer,
+    block: &[Span],
+    operator: Operator,
+    cursor: Location,
+    register: &mut Register,
+    shift_width: usize,
+) -> Location {
+    let top_left = block.first().map_or(cursor, |span| span.start);
+    let text = block
+        .iter()
+        .map(|span| buffer.text_between(span.start, span.end))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    match operator {
+        Operator::Yank => {
+            *register = Register {
+                text,
+                kind: RegisterKind::Blockwise,
+            };
+            return top_left;
+        }
+        Operator::Delete | Operator::Change => {
+            *register = Register {
+                text,
+                kind: RegisterKind::Blockwise,
+            };
+            for span in block {
+                buffer.replace_between(span.start, span.end, "", cursor);
+            }
+            // The change goes on in Insert mode
+            if operator == Operator::Change {
+                return top_left;
+            }
+        }
+        // The block moves right or left, the text before it stays
+        Operator::Indent => {
+            let indent = " ".repeat(shift_width);
+            for span in block.iter().filter(|span| span.start != span.end) {
+                buffer.insert_text(span.start, &indent, cursor);
+            }
+        }
+        Operator::Outdent => {
+            for span in block {
+                let text =
+                    buffer.text_between(span.start, end_of_line(buffer, span.start.line_index));
+                let blanks = shift(&text, false, shift_width);
+                let removed = text.chars().count().saturating_sub(blanks.chars().count());
+                let end = Location {
+                    grapheme_index: span.start.grapheme_index.saturating_add(removed),
+                    ..span.start
+                };
+                buffer.replace_between(span.start, end, "", cursor);
+            }
+        }
+        Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+            for span in block {
+                let text = buffer.text_between(span.start, span.end);
+                let changed = change_case(&text, operator);
+                if changed != text {
+                    buffer.replace_between(span.start, span.end, &changed, cursor);
+                }
+            }
+        }
+    }
+    buffer.commit();
+    top_left
+}
+
+/// Replaces each character of the spans with `replacement`, keeping the
+/// line breaks. Returns where the cursor goes, the start of the first span.
+pub fn replace(
+    buffer: &mut Buffer,
+    spans: &[Span],
+    replacement: char,
+    cursor: Location,
+) -> Location {
+    for span in spans {
+        let text = buffer.text_between(span.start, span.end);
+        let replaced: String = text
+            .graphemes(true)
+            .map(|grapheme| if grapheme == "\n" { '\n' } else { replacement })
+            .collect();
+        if replaced != text {
+            buffer.replace_between(span.start, span.end, &replaced, cursor);
+        }
+    }
+    buffer.commit();
+    spans.first().map_or(cursor, |span| span.start)
+}
+
+/// Joins the lines from `first` to `last` into one, like `J`. The indent of
+/// the joined lines is replaced by a space, unless the line before ends with
+/// one or the next starts with `)`. Returns where the cursor goes, where the
+/// last lines were joined, or `None` when there is no line to join.
+pub fn join(buffer: &mut Buffer, first: usize, last: usize, cursor: Location) -> Option<Location> {
+    let last = last.min(buffer.height().saturating_sub(1));
+    if last <= first {
+        return None;
+    }
+    let mut location = cursor;
+    for _ in first..last {
+        let line = buffer.line_text(first).unwrap_or_default().to_string();
+        let next_start = first_non_blank(buffer, first.saturating_add(1));
+        let next = buffer
+            .line_text(next_start.line_index)
+            .unwrap_or_default()
+            .trim_start()
+            .to_string();
+        let separator = if line.is_empty()
+            || line.ends_with([' ', '\t'])
+            || next.is_empty()
+            || next.starts_with(')')
+        {
+            ""
+        } else {
+            " "
+        };
+        location = end_of_line(buffer, first);
+        buffer.replace_between(location, next_start, separator, cursor);
+    }
+    buffer.commit();
+    Some(location)
+}
+
+/// Puts the text of `register` `count` times after the cursor, or before it
+/// with `before`. Whole lines go below or above the line of the cursor.
+/// Returns where the cursor goes, `None` if the register is empty.
+pub fn put(
+    buffer: &mut Buffer,
+    register: &Register,
+    cursor: Location,
+    before: bool,
+    count: usize,
+) -> Option<Location> {
+    if register.text.is_empty() {
+        return None;
+    }
+    let text = register.text.repeat(count);
+
+    let location = match register.kind {
+        RegisterKind::Blockwise => put_block(buffer, &register.text, cursor, before, count),
+        RegisterKind::Linewise => {
+            let line_index = if before {
+                cursor.line_index
+            } else {
+                cursor.line_index.saturating_add(1)
+            }
+            .min(buffer.height());
+            let at = Location {
+                line_index,
+                grapheme_index: 0,
+            };
+            buffer.insert_text(at, &text, cursor);
+            first_non_blank(buffer, line_index)
+        }
+        RegisterKind::Charwise => {
+            let line_length = buffer.grapheme_count(cursor.line_index);
+            let at = if before {
+                cursor
+            } else {
+                Location {
+                    grapheme_index: cursor.grapheme_index.saturating_add(1).min(line_length),
+                    ..cursor
+                }
+            };
+            // The cursor ends on the last character put
+            let after = buffer.insert_text(at, &text, cursor);
+            Location {
+                grapheme_index: after.grapheme_index.saturating_sub(1),
+                ..after
+            }
+        }
+    };
+    buffer.commit();
+    Some(location)
+}
+
+/// Puts each line of a block into a line of its own, from the column of the
+/// cursor or the one after it down. Lines too short are padded with spaces,
+/// and lines are added past the end of the text. Returns the top left corner
+/// of the block put.
+fn put_block(
+    buffer: &mut Buffer,
+    text: &str,
+    cursor: Location,
+    before: bool,
+    count: usize,
+) -> Location {
+    let line = buffer.line(cursor.line_index);
+    let grapheme_index = if before || buffer.is_line_empty(cursor.line_index) {
+        cursor.grapheme_index
+    } else {
+        cursor.grapheme_index.saturating_add(1)
+    };
+    let col = line.map_or(0, |line| line.width_until(grapheme_index));
+    let pieces: Vec<&str> = text.split('\n').collect();
+    let block_width = pieces.iter().map(|piece| piece.width()).max().unwrap_or(0);
+
+    let mut top_left = cursor;
+    for (offset, piece) in pieces.iter().enumerate() {
+        let line_index = cursor.line_index.saturating_add(offset);
+        if line_index >= buffer.height() {
+            let end = Location {
+                line_index: buffer.height(),
+                grapheme_index: 0,
+            };
+            buffer.insert_text(end, "\n", cursor);
+        }
+        let Some(line) = buffer.line(line_index) else {
+            break;
+        };
+        let length = buffer.grapheme_count(line_index);
+        let at_end = line.width() <= col;
+        let (grapheme_index, padding) = if at_end {
+            (length, col.saturating_sub(line.width()))
+        } else {
+            (line.grapheme_index_at(col), 0)
+        };
+        // Pieces are padded to the width of the block, but not at the end
+        let fill = block_width.saturating_sub(piece.width());
+        let mut piece = format!("{piece}{:fill$}", "").repeat(count);
+        if at_end {
+            piece.truncate(piece.len().saturating_sub(fill));
+        }
+        let at = Location {
+            grapheme_index,
+            line_index,
+        };
+        buffer.insert_text(at, &format!("{:padding$}{piece}", ""), cursor);
+        if offset == 0 {
+            top_left = Location {
+                grapheme_index: grapheme_index.saturating_add(padding),
+                ..at
+            };
+        }
+    }
+    top_left
+}
+
+/// The location one grapheme to the right, at most at the end of the line
+fn after(buffer: &Buffer, location: Location) -> Location {
+    buffer.clamp_to_line(Location {
+        grapheme_index: location.grapheme_index.saturating_add(1),
+        ..location
+    })
+}
+
+/// Indents a line by `shift_width` spaces, or removes as much indentation
+/// or a tab. Empty lines stay empty.
+fn shift(line: &str, indent: bool, shift_width: usize) -> String {
+    if indent {
+        if line.is_empty() {
+            return String::new();
+        }
+        return format!("{:shift_width$}{line}", "");
+    }
+    if let Some(rest) = line.strip_prefix('\t') {
+        return rest.to_string();
+    }
+    let spaces = line
+        .chars()
+        .take(shift_width)
+        .take_while(|&ch| ch == ' ')
+        .count();
+    line.get(spaces..).unwrap_or_default().to_string()
+}
+
+fn change_case(text: &str, operator: Operator) -> String {
+    match operator {
+        Operator::Lowercase => text.to_lowercase(),
+        Operator::Uppercase => text.to_uppercase(),
+        _ => text
+            .chars()
+            .map(|ch| {
+                if ch.is_uppercase() {
+                    ch.to_lowercase().collect::<String>()
+                } else {
+                    ch.to_uppercase().collect()
+                }
+            })
+            .collect(),
+    }
+}
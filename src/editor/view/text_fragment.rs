@@ -5,7 +5,7 @@ pub enum GraphemeWidth {
 }
 
 impl GraphemeWidth {
-    pub fn saturating_add(&self, other: usize) -> usize {
+    pub fn saturating_add(self, other: usize) -> usize {
         match self {
             Self::Half => other.saturating_add(1),
             Self::Full => other.saturating_add(2),
@@ -0,0 +1,513 @@
+use std::ops::Range;
+
+use super::{Buffer, Location, motion::words, operator::Span};
+use crate::editor::command::{TextObject, WordKind};
+
+/// A stretch of words, sentences or lines, and whether it is blank
+type Run = (Range<usize>, bool);
+
+/// What `object` covers around `from`, `count` of them where that makes
+/// sense. `None` if there is no such object there, like `i(` outside of
+/// any parentheses.
+pub fn span(
+    buffer: &Buffer,
+    from: Location,
+    object: TextObject,
+    around: bool,
+    count: Option<usize>,
+) -> Option<Span> {
+    if from.line_index >= buffer.height() {
+        return None;
+    }
+    let count = count.unwrap_or(1).max(1);
+    match object {
+        TextObject::Word(kind) => word(buffer, from, kind, around, count),
+        TextObject::Sentence => sentence(buffer, from, around, count),
+        TextObject::Paragraph => paragraph(buffer, from.line_index, around, count),
+        TextObject::Quote(quote) => quoted(buffer, from, quote, around),
+        TextObject::Bracket(open) => bracketed(buffer, from, open, around, count),
+        TextObject::Tag => tag(buffer, from, around, count),
+    }
+}
+
+/// Picks `count` runs from the one at `position`. Without `around` blank
+/// runs count too. With `around` only the others count, and the blank run
+/// after them comes along, or the one before them if there is none after.
+/// A blank run at the very start, like indentation, is never taken before.
+fn select(runs: &[Run], position: usize, around: bool, count: usize) -> Option<Range<usize>> {
+    let (first, on_blank) = runs.get(position)?.clone();
+    let mut last = position;
+    if around {
+        let mut taken = usize::from(!on_blank);
+        while taken < count {
+            let Some((_, blank)) = runs.get(last.saturating_add(1)) else {
+                break;
+            };
+            last = last.saturating_add(1);
+            taken = taken.saturating_add(usize::from(!blank));
+        }
+    } else {
+        last = position
+            .saturating_add(count.saturating_sub(1))
+            .min(runs.len().saturating_sub(1));
+    }
+    let mut range = first.start..runs.get(last)?.0.end;
+
+    if around && !on_blank {
+        match runs.get(last.saturating_add(1)) {
+            Some((next, true)) => range.end = next.end,
+            _ => {
+                if let Some((previous, true)) = position.checked_sub(1).and_then(|p| runs.get(p))
+                    && previous.start > 0
+                {
+                    range.start = previous.start;
+                }
+            }
+        }
+    }
+    Some(range)
+}
+
+/// `iw` and `aw`, within the line of the cursor
+fn word(
+    buffer: &Buffer,
+    from: Location,
+    kind: WordKind,
+    around: bool,
+    count: usize,
+) -> Option<Span> {
+    let line_index = from.line_index;
+    let length = buffer.grapheme_count(line_index);
+    let mut runs: Vec<Run> = Vec::new();
+    let mut index = 0;
+    for word in words(buffer, line_index, kind) {
+        if word.is_empty() {
+            continue;
+        }
+        if word.start > index {
+            runs.push((index..word.start, true));
+        }
+        index = word.end;
+        runs.push((word, false));
+    }
+    if length > index {
+        runs.push((index..length, true));
+    }
+
+    let grapheme_index = from.grapheme_index.min(length.checked_sub(1)?);
+    let position = runs
+        .iter()
+        .position(|(range, _)| range.contains(&grapheme_index))?;
+    let range = select(&runs, position, around, count)?;
+    Some(charwise(
+        Location {
+            line_index,
+            grapheme_index: range.start,
+        },
+        Location {
+            line_index,
+            grapheme_index: range.end,
+        },
+    ))
+}
+
+/// `is` and `as`. A sentence ends at a `.`, `!` or `?`, maybe followed by
+/// closing brackets or quotes, then white space. Sentences don't go past
+/// the paragraph.
+fn sentence(buffer: &Buffer, from: Location, around: bool, count: usize) -> Option<Span> {
+    let (first_line, last_line) = paragraph_lines(buffer, from.line_index)?;
+    let base = buffer.char_offset(Location {
+        line_index: first_line,
+        grapheme_index: 0,
+    });
+    let length = buffer
+        .char_offset(Location {
+            line_index: last_line,
+            grapheme_index: buffer.grapheme_count(last_line),
+        })
+        .saturating_sub(base);
+    let char_at = |index: usize| {
+        (index < length)
+            .then(|| buffer.chars_at(base.saturating_add(index)).next())
+            .flatten()
+    };
+    let is_blank = |index: usize| char_at(index).is_some_and(char::is_whitespace);
+    let cursor = buffer
+        .char_offset(from)
+        .saturating_sub(base)
+        .min(length.saturating_sub(1));
+
+    // The blanks after the last sentence end before the cursor start a run,
+    // so the runs are split up from there like from the paragraph start
+    let mut index = 0;
+    let mut blank = None;
+    let mut position = cursor.saturating_add(1);
+    for ch in buffer.chars_at(base.saturating_add(position)).reversed() {
+        let Some(previous) = position.checked_sub(1) else {
+            break;
+        };
+        position = previous;
+        if ch.is_whitespace() {
+            blank = Some(position);
+        } else if matches!(ch, '.' | '!' | '?') && blank.is_some() {
+            index = blank.unwrap_or_default();
+            break;
+        } else if !matches!(ch, ')' | ']' | '"' | '\'') {
+            blank = None;
+        }
+    }
+
+    // Enough runs for `select` to pick `count` of them after the blanks
+    let wanted = count.saturating_mul(2).saturating_add(2);
+    let mut runs: Vec<Run> = Vec::new();
+    while index < length && runs.len() < wanted {
+        let start = index;
+        if is_blank(index) {
+            while index < length && is_blank(index) {
+                index = index.saturating_add(1);
+            }
+            runs.push((start..index, true));
+            continue;
+        }
+        while let Some(ch) = char_at(index) {
+            index = index.saturating_add(1);
+            if matches!(ch, '.' | '!' | '?') {
+                let mut end = index;
+                while char_at(end).is_some_and(|ch| matches!(ch, ')' | ']' | '"' | '\'')) {
+                    end = end.saturating_add(1);
+                }
+                if end >= length || is_blank(end) {
+                    index = end;
+                    break;
+                }
+            }
+        }
+        // The last sentence may have no end, but still no trailing blanks
+        while index > start && is_blank(index.saturating_sub(1)) {
+            index = index.saturating_sub(1);
+        }
+        runs.push((start..index, false));
+    }
+
+    let position = runs.iter().position(|(range, _)| range.contains(&cursor))?;
+    let range = select(&runs, position, around, count)?;
+    Some(charwise(
+        buffer.location_of(base.saturating_add(range.start)),
+        buffer.location_of(base.saturating_add(range.end)),
+    ))
+}
+
+/// The first and last line of the paragraph around `line_index`. `None`
+/// on an empty line.
+fn paragraph_lines(buffer: &Buffer, line_index: usize) -> Option<(usize, usize)> {
+    let (lines, empty) = paragraph_run(buffer, line_index);
+    (!empty).then(|| (lines.start, lines.end.saturating_sub(1)))
+}
+
+/// The lines around `line_index` that are all empty or all not
+fn paragraph_run(buffer: &Buffer, line_index: usize) -> Run {
+    let empty = buffer.is_line_empty(line_index);
+    let mut first = line_index;
+    while first > 0 && buffer.is_line_empty(first.saturating_sub(1)) == empty {
+        first = first.saturating_sub(1);
+    }
+    let mut end = line_index.saturating_add(1);
+    while end < buffer.height() && buffer.is_line_empty(end) == empty {
+        end = end.saturating_add(1);
+    }
+    (first..end, empty)
+}
+
+/// `ip` and `ap`, where the empty lines between paragraphs count as
+/// paragraphs of their own for `ip`
+fn paragraph(buffer: &Buffer, line_index: usize, around: bool, count: usize) -> Option<Span> {
+    let current = paragraph_run(buffer, line_index);
+    let mut runs: Vec<Run> = Vec::new();
+    if let Some(previous) = current.0.start.checked_sub(1) {
+        runs.push(paragraph_run(buffer, previous));
+    }
+    let position = runs.len();
+    let mut end = current.0.end;
+    runs.push(current);
+    // Enough runs for `select` to pick `count` of them and the blank after
+    let wanted = count.saturating_mul(2).saturating_add(1);
+    while end < buffer.height() && runs.len().saturating_sub(position) < wanted {
+        let run = paragraph_run(buffer, end);
+        end = run.0.end;
+        runs.push(run);
+    }
+
+    let range = select(&runs, position, around, count)?;
+    Some(Span::lines(range.start, range.end.saturating_sub(1)))
+}
+
+/// `i"` and `a"`, within the line. The quotes pair up from the start of the
+/// line, skipping escaped ones. The pair around the cursor is taken, or the
+/// next one after it.
+fn quoted(buffer: &Buffer, from: Location, quote: char, around: bool) -> Option<Span> {
+    let line_start = buffer.char_offset(Location {
+        line_index: from.line_index,
+        grapheme_index: 0,
+    });
+    let cursor = buffer.char_offset(from).saturating_sub(line_start);
+
+    let mut pair = None;
+    let mut open = None;
+    let mut escaped = false;
+    let line = buffer.chars_at(line_start).take_while(|&ch| ch != '\n');
+    for (index, ch) in line.enumerate() {
+        if ch == quote && !escaped {
+            match open.take() {
+                Some(open) if cursor <= index => {
+                    pair = Some((open, index));
+                    break;
+                }
+                Some(_) => {}
+                None => open = Some(index),
+            }
+        }
+        escaped = ch == '\\' && !escaped;
+    }
+    let (open, close) = pair?;
+
+    let (mut start, mut end) = if around {
+        (open, close.saturating_add(1))
+    } else {
+        (open.saturating_add(1), close)
+    };
+    if around {
+        let is_blank = |index: usize| {
+            buffer
+                .chars_at(line_start.saturating_add(index))
+                .next()
+                .is_some_and(|ch| ch.is_whitespace() && ch != '\n')
+        };
+        if is_blank(end) {
+            while is_blank(end) {
+                end = end.saturating_add(1);
+            }
+        } else {
+            while start > 0 && is_blank(start.saturating_sub(1)) {
+                start = start.saturating_sub(1);
+            }
+        }
+    }
+    Some(charwise(
+        buffer.location_of(line_start.saturating_add(start)),
+        buffer.location_of(line_start.saturating_add(end)),
+    ))
+}
+
+/// `i(` and `a(` and the like, given the opening bracket. The `count`-th
+/// unmatched opening bracket before the cursor is matched with its closing
+/// bracket, over as many lines as it takes. Inside a block whose brackets
+/// are on lines of their own, `i(` takes the lines in between.
+fn bracketed(
+    buffer: &Buffer,
+    from: Location,
+    open: char,
+    around: bool,
+    count: usize,
+) -> Option<Span> {
+    let close = match open {
+        '(' => ')',
+        '[' => ']',
+        '{' => '}',
+        _ => '>',
+    };
+    let cursor = buffer.char_offset(from);
+    // On the opening bracket, that bracket is the first one found
+    let scan_from = if buffer.chars_at(cursor).next() == Some(open) {
+        cursor.saturating_add(1)
+    } else {
+        cursor
+    };
+
+    let mut depth: usize = 0;
+    let mut remaining = count;
+    let mut open_index = scan_from;
+    let mut chars = buffer.chars_at(scan_from).reversed();
+    loop {
+        let ch = chars.next()?;
+        open_index = open_index.saturating_sub(1);
+        if ch == close {
+            depth = depth.saturating_add(1);
+        } else if ch == open {
+            if depth == 0 {
+                remaining = remaining.saturating_sub(1);
+                if remaining == 0 {
+                    break;
+                }
+            } else {
+                depth = depth.saturating_sub(1);
+            }
+        }
+    }
+
+    let mut close_index = open_index;
+    for ch in buffer.chars_at(open_index.saturating_add(1)) {
+        close_index = close_index.saturating_add(1);
+        if ch == open {
+            depth = depth.saturating_add(1);
+        } else if ch == close {
+            if depth == 0 {
+                break;
+            }
+            depth = depth.saturating_sub(1);
+        }
+    }
+    if buffer.chars_at(close_index).next() != Some(close) {
+        return None;
+    }
+
+    if around {
+        return Some(charwise(
+            buffer.location_of(open_index),
+            buffer.location_of(close_index.saturating_add(1)),
+        ));
+    }
+    let start = buffer.location_of(open_index.saturating_add(1));
+    let end = buffer.location_of(close_index);
+    let ends_line = buffer.chars_at(open_index.saturating_add(1)).next() == Some('\n');
+    let close_line_start = buffer.char_offset(Location {
+        line_index: end.line_index,
+        grapheme_index: 0,
+    });
+    let starts_line = buffer
+        .chars_at(close_line_start)
+        .take(close_index.saturating_sub(close_line_start))
+        .all(char::is_whitespace);
+    // The brackets are on lines of their own, so the lines in between are taken
+    if ends_line && starts_line {
+        let first = start.line_index.saturating_add(1);
+        if end.line_index > first {
+            return Some(Span::lines(first, end.line_index.saturating_sub(1)));
+        }
+        return Some(charwise(end, end));
+    }
+    Some(charwise(start, end))
+}
+
+/// An opening or closing tag, with the chars it covers
+struct Tag {
+    name: String,
+    closing: bool,
+    range: Range<usize>,
+}
+
+/// `it` and `at`. Tags are matched by name, skipping self-closing tags,
+/// comments and declarations. Going back from the cursor, the `count`-th
+/// opening tag that isn't closed before the cursor is taken, with the tag
+/// that closes it.
+fn tag(buffer: &Buffer, from: Location, around: bool, count: usize) -> Option<Span> {
+    let cursor = buffer.char_offset(from);
+    // Names of the tags closed before the cursor, nearest last
+    let mut closed: Vec<String> = Vec::new();
+    // Names without a closing tag after the cursor. Tags of the same name
+    // further back can't be closed either, so they aren't looked for again.
+    let mut unclosed: Vec<String> = Vec::new();
+    let mut remaining = count;
+    let mut before = cursor.saturating_add(1);
+    let (open, close) = loop {
+        let offset = buffer
+            .chars_at(before)
+            .reversed()
+            .position(|ch| ch == '<')?;
+        before = before.saturating_sub(offset).saturating_sub(1);
+        let Some(tag) = parse_tag(buffer, before) else {
+            continue;
+        };
+        if tag.closing {
+            // A closing tag around the cursor closes one of the tags looked for
+            if tag.range.end <= cursor {
+                closed.push(tag.name);
+            }
+        } else if let Some(position) = closed.iter().rposition(|name| *name == tag.name) {
+            closed.truncate(position);
+        } else if !unclosed.contains(&tag.name) {
+            match closing_tag(buffer, &tag) {
+                Some(close) if cursor < close.end => {
+                    remaining = remaining.saturating_sub(1);
+                    if remaining == 0 {
+                        break (tag.range, close);
+                    }
+                }
+                Some(_) => {}
+                None => unclosed.push(tag.name),
+            }
+        }
+    };
+
+    let (start, end) = if around {
+        (open.start, close.end)
+    } else {
+        (open.end, close.start)
+    };
+    Some(charwise(buffer.location_of(start), buffer.location_of(end)))
+}
+
+/// The tag starting with the `<` at `start`. `None` if it's not a tag, or
+/// a tag that doesn't pair up.
+fn parse_tag(buffer: &Buffer, start: usize) -> Option<Tag> {
+    let mut inside = String::new();
+    let mut chars = buffer.chars_at(start.saturating_add(1));
+    loop {
+        match chars.next()? {
+            '<' => return None,
+            '>' => break,
+            ch => inside.push(ch),
+        }
+    }
+    if inside.starts_with(['!', '?']) || inside.ends_with('/') {
+        return None;
+    }
+    let end = start
+        .saturating_add(inside.chars().count())
+        .saturating_add(2);
+
+    let (closing, name) = match inside.strip_prefix('/') {
+        Some(name) => (true, name.trim()),
+        None => (
+            false,
+            inside.split(char::is_whitespace).next().unwrap_or_default(),
+        ),
+    };
+    (!name.is_empty()).then(|| Tag {
+        name: name.to_string(),
+        closing,
+        range: start..end,
+    })
+}
+
+/// Where the tag closing `open` is, skipping the tags of the same name
+/// opened and closed in between
+fn closing_tag(buffer: &Buffer, open: &Tag) -> Option<Range<usize>> {
+    let mut depth: usize = 0;
+    let mut index = open.range.end;
+    loop {
+        let offset = buffer.chars_at(index).position(|ch| ch == '<')?;
+        let start = index.saturating_add(offset);
+        index = start.saturating_add(1);
+        let Some(tag) = parse_tag(buffer, start) else {
+            continue;
+        };
+        if tag.name != open.name {
+            continue;
+        }
+        if !tag.closing {
+            depth = depth.saturating_add(1);
+        } else if depth == 0 {
+            return Some(tag.range);
+        } else {
+            depth = depth.saturating_sub(1);
+        }
+    }
+}
+
+const fn charwise(start: Location, end: Location) -> Span {
+    Span {
+        start,
+        end,
+        linewise: false,
+    }
+}
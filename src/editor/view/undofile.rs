@@ -0,0 +1,98 @@
+use std::{
+    borrow::Cow,
+    env,
+    fs::{self, File},
+    io::{BufReader, Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+
+use super::{fileio, history::History};
+
+const UNDO_FILE_VERSION: u32 = 1;
+
+/// On-disk representation of the undo history of a file.
+///
+/// The history only applies to the exact text it was saved with, so the
+/// canonical path and a hash of the content are stored alongside it.
+#[derive(Serialize, Deserialize)]
+struct UndoFile<'a> {
+    version: u32,
+    path: PathBuf,
+    hash: u64,
+    history: Cow<'a, History>,
+}
+
+/// Writes the history of the file at `path`, whose content is `text`
+pub fn write(path: &Path, text: &Rope, history: &History) -> Result<(), Error> {
+    let path = fs::canonicalize(path)?;
+    let undo_path = undo_file_path(&path)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "No directory for undo files"))?;
+    if let Some(directory) = undo_path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+
+    let undo_file = UndoFile {
+        version: UNDO_FILE_VERSION,
+        path,
+        hash: hash(text),
+        history: Cow::Borrowed(history),
+    };
+
+    // The history holds every text deleted from the file, so it must not
+    // be readable by anyone the file itself isn't
+    fileio::write_private(&undo_path, |writer| {
+        serde_json::to_writer(writer, &undo_file)?;
+        Ok(0)
+    })
+    .map(|_| ())
+}
+
+/// Reads the history of the file at `path`. Returns `None` when there is
+/// none, or when it was saved for a different content, e.g. because the file
+/// got changed outside the editor.
+pub fn read(path: &Path, text: &Rope) -> Option<History> {
+    let path = fs::canonicalize(path).ok()?;
+    let reader = BufReader::new(File::open(undo_file_path(&path)?).ok()?);
+    let undo_file: UndoFile = serde_json::from_reader(reader).ok()?;
+
+    if undo_file.version != UNDO_FILE_VERSION
+        || undo_file.path != path
+        || undo_file.hash != hash(text)
+        || !undo_file.history.is_consistent()
+    {
+        return None;
+    }
+
+    Some(undo_file.history.into_owned())
+}
+
+/// Undo files live in `$XDG_STATE_HOME/kedit/undo`, named after the
+/// canonical path of the file with `%` written as `%%`, `/` as `%+` and `\`
+/// as `%-`, so that no two paths share a name
+fn undo_file_path(path: &Path) -> Option<PathBuf> {
+    let state_home = env::var_os("XDG_STATE_HOME")
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+    let name = path
+        .to_string_lossy()
+        .replace('%', "%%")
+        .replace('/', "%+")
+        .replace('\\', "%-");
+
+    Some(state_home.join(crate::editor::NAME).join("undo").join(name))
+}
+
+/// 64-bit FNV-1a hash of the text, stable across builds and platforms
+fn hash(text: &Rope) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    text.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
@@ -0,0 +1,240 @@
+use std::ops::Range;
+
+use super::{Buffer, Location, motion::is_before, operator::Span};
+use crate::editor::command::{Motion, VisualKind};
+
+/// Motions with their counts
+pub type Motions = Vec<(Motion, Option<usize>)>;
+
+/// The text selected in Visual mode, from `anchor` where the selection
+/// started to the cursor
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub anchor: Location,
+    pub kind: VisualKind,
+}
+
+impl Selection {
+    /// The two ends of the selection in the order of the text
+    fn ends(self, cursor: Location) -> (Location, Location) {
+        if is_before(cursor, self.anchor) {
+            (cursor, self.anchor)
+        } else {
+            (self.anchor, cursor)
+        }
+    }
+
+    /// The selected text of a characterwise or linewise selection. The
+    /// character under the cursor is part of it, and past the end of a line
+    /// so is the line break.
+    pub fn span(self, buffer: &Buffer, cursor: Location) -> Span {
+        let (start, end) = self.ends(cursor);
+        let last_line = buffer.height().saturating_sub(1);
+        if self.kind == VisualKind::Line {
+            return Span::lines(
+                start.line_index.min(last_line),
+                end.line_index.min(last_line),
+            );
+        }
+
+        let length = buffer.grapheme_count(end.line_index);
+        let end = if end.grapheme_index >= length && end.line_index < last_line {
+            Location {
+                line_index: end.line_index.saturating_add(1),
+                grapheme_index: 0,
+            }
+        } else {
+            Location {
+                grapheme_index: end.grapheme_index.saturating_add(1).min(length),
+                ..end
+            }
+        };
+        Span {
+            start,
+            end,
+            linewise: false,
+        }
+    }
+
+    /// The motions selecting as much text again from the start of the
+    /// selection, for `.` to repeat a change made in Visual mode: as many
+    /// lines, and as many characters on a single line or up to the same
+    /// column on the last line
+    pub fn motions(self, cursor: Location) -> Motions {
+        let (start, end) = self.ends(cursor);
+        let mut motions = Vec::new();
+        let lines = end.line_index.saturating_sub(start.line_index);
+        if lines > 0 {
+            motions.push((Motion::Down, Some(lines)));
+        }
+        let columns = match self.kind {
+            VisualKind::Line => 0,
+            VisualKind::Char if lines == 0 => {
+                end.grapheme_index.saturating_sub(start.grapheme_index)
+            }
+            VisualKind::Char => {
+                motions.push((Motion::LineStart, None));
+                end.grapheme_index
+            }
+            VisualKind::Block => cursor.grapheme_index.abs_diff(self.anchor.grapheme_index),
+        };
+        if columns > 0 {
+            motions.push((Motion::Right, Some(columns)));
+        }
+        motions
+    }
+
+    /// The selected text of a blockwise selection, a span for each line
+    pub fn block(self, buffer: &Buffer, cursor: Location) -> Vec<Span> {
+        let (start, end) = self.ends(cursor);
+        let columns = self.block_columns(buffer, cursor);
+        let last_line = end.line_index.min(buffer.height().saturating_sub(1));
+        (start.line_index..=last_line)
+            .map(|line_index| {
+                let range = block_range(buffer, line_index, columns);
+                Span {
+                    start: Location {
+                        line_index,
+                        grapheme_index: range.start,
+                    },
+                    end: Location {
+                        line_index,
+                        grapheme_index: range.end,
+                    },
+                    linewise: false,
+                }
+            })
+            .collect()
+    }
+
+    /// The screen columns of a block from the left to the right one, and
+    /// whether it goes on to the end of every line, after `$`
+    fn block_columns(self, buffer: &Buffer, cursor: Location) -> (usize, usize, bool) {
+        let left = |location: Location| {
+            buffer
+                .line(location.line_index)
+                .map_or(0, |line| line.width_until(location.grapheme_index))
+        };
+        // All of a wide character is in the block
+        let right = |location: Location| {
+            buffer.line(location.line_index).map_or(0, |line| {
+                line.width_until(location.grapheme_index.saturating_add(1))
+                    .saturating_sub(1)
+            })
+        };
+        let length = buffer.grapheme_count(cursor.line_index);
+        let to_end = length > 0 && cursor.grapheme_index >= length;
+        (
+            left(self.anchor).min(left(cursor)),
+            right(self.anchor).max(right(cursor)),
+            to_end,
+        )
+    }
+
+    /// The screen columns to highlight on a line, `None` if it is not part
+    /// of the selection. A selected line break shows as one more column.
+    pub fn columns(
+        self,
+        buffer: &Buffer,
+        cursor: Location,
+        line_index: usize,
+    ) -> Option<Range<usize>> {
+        let (start, end) = self.ends(cursor);
+        if line_index < start.line_index || line_index > end.line_index {
+            return None;
+        }
+        let line = buffer.line(line_index)?;
+        let width = line.width();
+
+        let columns = match self.kind {
+            VisualKind::Line => 0..width.max(1),
+            VisualKind::Char => {
+                let first = if line_index == start.line_index {
+                    line.width_until(start.grapheme_index)
+                } else {
+                    0
+                };
+                let last = if line_index == end.line_index
+                    && end.grapheme_index < buffer.grapheme_count(line_index)
+                {
+                    line.width_until(end.grapheme_index.saturating_add(1))
+                } else {
+                    width.saturating_add(1)
+                };
+                first..last
+            }
+            VisualKind::Block => {
+                let range = block_range(buffer, line_index, self.block_columns(buffer, cursor));
+                line.width_until(range.start)..line.width_until(range.end)
+            }
+        };
+        Some(columns)
+    }
+}
+
+/// The graphemes of a line within the columns of a block
+fn block_range(
+    buffer: &Buffer,
+    line_index: usize,
+    (left, right, to_end): (usize, usize, bool),
+) -> Range<usize> {
+    let Some(line) = buffer.line(line_index) else {
+        return Range { start: 0, end: 0 };
+    };
+    let length = buffer.grapheme_count(line_index);
+    let start = line.grapheme_index_at(left);
+    let end = if to_end {
+        length
+    } else {
+        line.grapheme_index_at(right).saturating_add(1).min(length)
+    };
+    start..end.max(start)
+}
+
+/// After `c` on a block, the text typed on the first line goes into the
+/// other lines of the block when leaving Insert mode
+pub struct BlockInsert {
+    at: Location,
+    others: Vec<Location>,
+    length: usize,
+}
+
+impl BlockInsert {
+    /// Right after the block was deleted, before anything is typed
+    pub fn new(buffer: &Buffer, block: &[Span]) -> Option<Self> {
+        let (first, rest) = block.split_first()?;
+        Some(Self {
+            at: first.start,
+            others: rest
+                .iter()
+                .filter(|span| span.start != span.end)
+                .map(|span| span.start)
+                .collect(),
+            length: buffer.grapheme_count(first.start.line_index),
+        })
+    }
+
+    /// Inserts what was typed into the other lines. Nothing happens if the
+    /// cursor left the line, e.g. after a line break was typed.
+    pub fn finish(&self, buffer: &mut Buffer, cursor: Location) {
+        if cursor.line_index != self.at.line_index {
+            return;
+        }
+        let typed = buffer
+            .grapheme_count(self.at.line_index)
+            .saturating_sub(self.length);
+        if typed == 0 {
+            return;
+        }
+        let text = buffer.text_between(
+            self.at,
+            Location {
+                grapheme_index: self.at.grapheme_index.saturating_add(typed),
+                ..self.at
+            },
+        );
+        for &at in &self.others {
+            buffer.insert_text(at, &text, cursor);
+        }
+    }
+}